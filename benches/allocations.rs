@@ -0,0 +1,72 @@
+use animikiikode::stdlib::StdLib;
+use animikiikode::{parse, Interpreter, Value};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Counts heap allocations so each case can report how many one evaluation
+// makes. Timing is left to the criterion benchmarks.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const RUNS: usize = 1000;
+
+const CASES: [(&str, &str); 3] = [
+    ("nested arithmetic", "((a + b) * (a - b) + a * a) % (b + 7)"),
+    (
+        "nested if",
+        "if a < b { if a * 2 > b { a + b } else { a - b } } else { -(a * b) }",
+    ),
+    ("function calls", "square(a) + square(b) * square(a - b)"),
+];
+
+fn main() {
+    let mut interpreter = Interpreter::new();
+    interpreter.define("a", Value::Integer(6));
+    interpreter.define("b", Value::Integer(9));
+    interpreter
+        .eval("func square(x: i32) -> i32 { let y = x; y * y }")
+        .unwrap();
+
+    // The first check builds the name set; only later ones are counted
+    StdLib::is_builtin("println");
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..RUNS {
+        std::hint::black_box(StdLib::is_builtin(std::hint::black_box("println")));
+    }
+    report(
+        "builtin name check",
+        ALLOCATIONS.load(Ordering::Relaxed) - before,
+    );
+
+    for (label, source) in CASES {
+        let node = parse(source).unwrap().remove(0);
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        for _ in 0..RUNS {
+            interpreter.interpret(&node).unwrap();
+        }
+        report(label, ALLOCATIONS.load(Ordering::Relaxed) - before);
+    }
+}
+
+fn report(label: &str, allocations: usize) {
+    println!(
+        "{:<20} {:>6.1} allocations per evaluation",
+        label,
+        allocations as f64 / RUNS as f64
+    );
+}
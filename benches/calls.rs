@@ -0,0 +1,24 @@
+use animikiikode::Interpreter;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const FIB: &str = "func fib(n: i32) -> i32 { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }";
+
+// Nested scopes stay alive for the whole descent, so this measures the cost
+// of entering a scope when the chain of enclosing frames is deep
+const DEPTH: &str = "func depth(n: i32) -> i32 { if n == 0 { 0 } else { 1 + depth(n - 1) } }";
+
+fn recursive_calls(c: &mut Criterion) {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval(FIB).unwrap();
+    interpreter.eval(DEPTH).unwrap();
+
+    c.bench_function("fib_20", |b| {
+        b.iter(|| interpreter.eval("fib(20)").unwrap())
+    });
+    c.bench_function("depth_900", |b| {
+        b.iter(|| interpreter.eval("depth(900)").unwrap())
+    });
+}
+
+criterion_group!(benches, recursive_calls);
+criterion_main!(benches);
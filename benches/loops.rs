@@ -0,0 +1,49 @@
+use animikiikode::Interpreter;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn counting_loop(iterations: u32) -> String {
+    format!(
+        "let i = 0; let total = 0; while i < {} {{ total += i % 7; i += 1 }}",
+        iterations
+    )
+}
+
+fn run_loop(source: &str, bytecode: bool) {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_bytecode(bytecode);
+    interpreter.eval(source).unwrap();
+}
+
+fn tree_walk_vs_bytecode(c: &mut Criterion) {
+    let source = counting_loop(10_000);
+    let mut group = c.benchmark_group("counting_loop");
+    group.bench_function("tree_walk", |b| b.iter(|| run_loop(&source, false)));
+    group.bench_function("bytecode", |b| b.iter(|| run_loop(&source, true)));
+    group.finish();
+}
+
+// Long enough that any per-iteration cost of re-running the loop body, such
+// as copying its AST, dominates the measurement
+fn million_iterations(c: &mut Criterion) {
+    let source = counting_loop(1_000_000);
+    let mut group = c.benchmark_group("million_iterations");
+    group.sample_size(10);
+    group.bench_function("tree_walk", |b| b.iter(|| run_loop(&source, false)));
+    group.bench_function("bytecode", |b| b.iter(|| run_loop(&source, true)));
+    group.finish();
+}
+
+// Reads several variables on every iteration, so environment lookups dominate
+fn variable_reads(c: &mut Criterion) {
+    let source = "let a = 1; let b = 2; let c = 3; let d = 4; let i = 0; let sum = 0; \
+                  while i < 10000 { sum = a + b * c - d + sum % 100; i += a }";
+    c.bench_function("variable_reads", |b| b.iter(|| run_loop(source, false)));
+}
+
+criterion_group!(
+    benches,
+    tree_walk_vs_bytecode,
+    million_iterations,
+    variable_reads
+);
+criterion_main!(benches);
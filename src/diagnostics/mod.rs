@@ -0,0 +1,65 @@
+use crate::lexer::Position;
+use std::io::IsTerminal;
+
+const RED: &str = "\x1b[31m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// Colors are only used on a terminal, and never when NO_COLOR is set
+pub fn use_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+}
+
+// An error message and, when it's known, where in the source it starts
+pub type Diagnostic = (String, Option<Position>);
+
+// Formats an error, followed by the offending source line with a caret under
+// `position` when it points inside `source`
+pub fn render(source: &str, message: &str, position: Option<Position>, color: bool) -> String {
+    let (red, bold, reset) = if color {
+        (RED, BOLD, RESET)
+    } else {
+        ("", "", "")
+    };
+    let mut out = format!("{bold}{red}error{reset}{bold}: {message}{reset}\n");
+
+    let Some((line, column)) = position else {
+        return out;
+    };
+    let Some(text) = line.checked_sub(1).and_then(|i| source.lines().nth(i)) else {
+        return out;
+    };
+
+    let gutter = " ".repeat(line.to_string().len());
+    let padding = " ".repeat(column.saturating_sub(1));
+    out.push_str(&format!(
+        "{gutter} |\n{line} | {text}\n{gutter} | {padding}{red}^{reset}\n"
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_points_at_column() {
+        let source = "let a = 1;\nlet b = a & 2;\n";
+        assert_eq!(
+            render(source, "Invalid character: &", Some((2, 11)), false),
+            "error: Invalid character: &\n  \
+             |\n\
+             2 | let b = a & 2;\n  \
+             |           ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_without_position() {
+        assert_eq!(
+            render("x", "Undefined variable: y", None, false),
+            "error: Undefined variable: y\n"
+        );
+        assert_eq!(render("x", "oops", Some((5, 1)), false), "error: oops\n");
+    }
+}
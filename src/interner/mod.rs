@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+// An interned identifier. The parser maps every name to a symbol once, so
+// scopes can hash and compare a u32 instead of the whole string.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+// Names are leaked so symbols can hand out `&'static str`. Only names
+// written in source are interned, and programs only contain so many distinct
+// identifiers, so the table stays small. It's shared by every thread, so a
+// symbol means the same name wherever it's used.
+#[derive(Default)]
+struct Interner {
+    names: Vec<&'static str>,
+    symbols: HashMap<&'static str, Symbol>,
+}
+
+static INTERNER: LazyLock<RwLock<Interner>> = LazyLock::new(Default::default);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        // The table never holds a half-made entry, so a poisoned lock is fine
+        if let Some(&symbol) = INTERNER
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .symbols
+            .get(name)
+        {
+            return symbol;
+        }
+        let mut interner = INTERNER.write().unwrap_or_else(PoisonError::into_inner);
+        // Another thread may have interned it between the two locks
+        if let Some(&symbol) = interner.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(interner.names.len() as u32);
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+// Both print the name, so ASTs and errors read the same as with plain strings
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// Symbols are small sequential integers, so a multiplicative hash spreads
+// them well without SipHash's cost. Only meant for keys that are symbols.
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u32(byte as u32);
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.0 = (self.0.rotate_left(5) ^ n as u64).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+pub type SymbolMap<V> = HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>;
+pub type SymbolSet = HashSet<Symbol, BuildHasherDefault<SymbolHasher>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning_is_stable() {
+        let a = Symbol::intern("counter");
+        assert_eq!(Symbol::intern("counter"), a);
+        assert_ne!(Symbol::intern("count"), a);
+        assert_eq!(a.as_str(), "counter");
+        assert_eq!(format!("{} {:?}", a, a), "counter \"counter\"");
+    }
+
+    #[test]
+    fn test_symbols_mean_the_same_name_on_every_thread() {
+        let here = Symbol::intern("shared_across_threads");
+        let (there, name) =
+            std::thread::spawn(move || (Symbol::intern("shared_across_threads"), here.as_str()))
+                .join()
+                .unwrap();
+        assert_eq!(there, here);
+        assert_eq!(name, "shared_across_threads");
+    }
+}
@@ -1,8 +1,20 @@
 #![allow(dead_code)]
 
-use crate::parser::{AstNode, Operator, Type, UnaryOperator};
-use crate::stdlib::StdLib;
-use std::collections::HashMap;
+use crate::diagnostics::Diagnostic;
+use crate::interner::{Symbol, SymbolMap, SymbolSet};
+use crate::optimizer::fold_constants;
+use crate::parser::{
+    AstNode, Attribute, Operator, Ownership, Param, Pattern, Type, UnaryOperator, Variant,
+};
+use crate::resolver::Resolver;
+use crate::stdlib::{Capability, Io, StdLib};
+use crate::vm;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::Write;
+use std::rc::{self, Rc};
+use std::time::{Duration, Instant};
 
 // Values that can exist during runtime
 #[derive(Debug, Clone, PartialEq)]
@@ -13,141 +25,1193 @@ pub enum Value {
     Boolean(bool),
     Vector(Vec<Value>),
     HashMap(HashMap<String, Value>),
-    Unit,             // For functions that don't return a value
-    Reference(usize), // For heap allocated values
+    Tuple(Vec<Value>),
+    Unit, // For functions that don't return a value
+    // The body is shared so calling a function doesn't copy its AST
     Function {
-        params: Vec<(String, Type)>,
-        body: Box<AstNode>,
-        closure: Environment,
+        params: Vec<Param>,
+        defaults: Rc<[AstNode]>,
+        body: Rc<AstNode>,
+        closure: Closure,
+        is_async: bool,
+        attributes: Vec<Attribute>,
     },
+    // The result of calling an async function, produced when awaited
+    Future(Future),
+    Channel(Channel),
+    // A running `#actor` function; sending to it queues a message
+    Actor(Actor),
+    // The value of an `@` binding, and a `#weak` binding that points at one
+    Shared(Shared),
+    Weak(WeakRef),
+    EnumVariant {
+        enum_name: Symbol,
+        variant: Symbol,
+        payload: Option<Box<Value>>,
+    },
+    // What a `try_` builtin returns: its value, or the error it failed with
+    Result(Result<Box<Value>, String>),
+    // A value that may be missing, such as the result of a lookup
+    Optional(Option<Box<Value>>),
+}
+
+// The environment a function was defined in, shared with the defining scope so
+// captured variables can be mutated. Functions live in the environments they
+// capture, so equality is by identity and Debug output stays shallow to avoid
+// walking the resulting cycles. Scopes break those cycles as they exit.
+#[derive(Clone)]
+pub struct Closure(pub Rc<RefCell<Environment>>);
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Closure")
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// A call to an async function that runs the first time it is awaited.
+// Copies share state, so the body runs once however often it's awaited.
+#[derive(Clone)]
+pub struct Future(Rc<RefCell<FutureState>>);
+
+enum FutureState {
+    Pending {
+        name: Symbol,
+        params: Vec<Param>,
+        body: Rc<AstNode>,
+        closure: Closure,
+        args: Vec<Value>,
+    },
+    Ready(Value),
+}
+
+impl fmt::Debug for Future {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Future")
+    }
+}
+
+impl PartialEq for Future {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// A queue of values. Copies share the queue, so a channel can be handed to
+// other functions and actors.
+#[derive(Clone, Default)]
+pub struct Channel(Rc<RefCell<VecDeque<Value>>>);
+
+impl Channel {
+    fn send(&self, value: Value) {
+        self.0.borrow_mut().push_back(value);
+    }
+
+    fn try_recv(&self) -> Option<Value> {
+        self.0.borrow_mut().pop_front()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Channel")
+    }
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// An actor owns the scope its spawn arguments were bound in, so its state
+// persists between messages and isn't shared with other actors. The body
+// handles one message from the mailbox at a time.
+#[derive(Clone)]
+pub struct Actor(Rc<ActorState>);
+
+struct ActorState {
+    name: Symbol,
+    mailbox: Channel,
+    scope: Closure,
+    message: Param,
+    body: Rc<AstNode>,
+}
+
+impl fmt::Debug for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Actor")
+    }
+}
+
+impl PartialEq for Actor {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// A heap value shared by every binding that holds it, freed once the last
+// of them goes away
+#[derive(Clone)]
+pub struct Shared(Rc<RefCell<Value>>);
+
+// Points at a shared value without keeping it alive
+#[derive(Clone)]
+pub struct WeakRef(rc::Weak<RefCell<Value>>);
+
+impl Shared {
+    pub fn new(value: Value) -> Self {
+        Shared(Rc::new(RefCell::new(value)))
+    }
+
+    pub fn get(&self) -> Value {
+        self.0.borrow().clone()
+    }
+
+    pub fn downgrade(&self) -> WeakRef {
+        WeakRef(Rc::downgrade(&self.0))
+    }
+}
+
+impl WeakRef {
+    // None once every strong owner is gone
+    pub fn get(&self) -> Option<Value> {
+        self.0.upgrade().map(|value| value.borrow().clone())
+    }
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Shared({:?})", self.0.borrow())
+    }
+}
+
+impl PartialEq for Shared {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for WeakRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Weak")
+    }
+}
+
+impl PartialEq for WeakRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Vector(_) => "vector",
+            Value::HashMap(_) => "hashmap",
+            Value::Tuple(_) => "tuple",
+            Value::Unit => "unit",
+            Value::Function { .. } => "function",
+            Value::Future(_) => "future",
+            Value::Channel(_) => "channel",
+            Value::Actor(_) => "actor",
+            Value::Shared(_) => "shared",
+            Value::Weak(_) => "weak",
+            Value::EnumVariant { .. } => "enum",
+            Value::Result(_) => "result",
+            Value::Optional(_) => "optional",
+        }
+    }
+
+    // Whether a `#sync` function may capture this value. Shared and weak
+    // values, channels, actors and futures point into this thread's heap, so
+    // they can't be shared, and neither can functions other than `#sync`
+    // ones, whose captures have already been checked.
+    pub fn is_shareable(&self) -> bool {
+        match self {
+            Value::Shared(_)
+            | Value::Weak(_)
+            | Value::Channel(_)
+            | Value::Actor(_)
+            | Value::Future(_) => false,
+            Value::Function { attributes, .. } => attributes.contains(&Attribute::Sync),
+            Value::Vector(items) | Value::Tuple(items) => items.iter().all(Value::is_shareable),
+            Value::HashMap(map) => map.values().all(Value::is_shareable),
+            Value::EnumVariant {
+                payload: Some(payload),
+                ..
+            } => payload.is_shareable(),
+            Value::Result(Ok(value)) | Value::Optional(Some(value)) => value.is_shareable(),
+            _ => true,
+        }
+    }
+
+    // How many functions held directly in this value capture `scope`. Values
+    // behind an `Rc` of their own, such as shared values and futures, may be
+    // held elsewhere too, so they aren't counted.
+    fn captures(&self, scope: &Rc<RefCell<Environment>>) -> usize {
+        match self {
+            Value::Function { closure, .. } => Rc::ptr_eq(&closure.0, scope) as usize,
+            Value::Vector(items) | Value::Tuple(items) => {
+                items.iter().map(|item| item.captures(scope)).sum()
+            }
+            Value::HashMap(map) => map.values().map(|value| value.captures(scope)).sum(),
+            Value::EnumVariant {
+                payload: Some(payload),
+                ..
+            } => payload.captures(scope),
+            Value::Result(Ok(value)) | Value::Optional(Some(value)) => value.captures(scope),
+            _ => 0,
+        }
+    }
+
+    // The type a `let` without an annotation records for this value, if any.
+    // Collections are only typed by their shape, not their elements.
+    pub fn inferred_type(&self) -> Option<Type> {
+        match self {
+            Value::Integer(_) => Some(Type::I32),
+            Value::Float(_) => Some(Type::F64),
+            Value::String(_) => Some(Type::String),
+            Value::Boolean(_) => Some(Type::Bool),
+            Value::Vector(_) => Some(Type::Vec(Box::new(Type::Dynamic))),
+            Value::HashMap(_) => Some(Type::HashMap(
+                Box::new(Type::String),
+                Box::new(Type::Dynamic),
+            )),
+            Value::Tuple(items) => Some(Type::Tuple(
+                items
+                    .iter()
+                    .map(|item| item.inferred_type().unwrap_or(Type::Dynamic))
+                    .collect(),
+            )),
+            Value::Shared(shared) => shared
+                .0
+                .borrow()
+                .inferred_type()
+                .map(|ty| Type::Shared(Box::new(ty))),
+            Value::EnumVariant { enum_name, .. } => Some(Type::Named(*enum_name)),
+            _ => None,
+        }
+    }
+
+    // Whether this value can be stored in a binding of type `ty`
+    pub fn has_type(&self, ty: &Type) -> bool {
+        match (ty, self) {
+            (Type::Dynamic, _) => true,
+            // Integers are stored as i32, so sized types check the range
+            (Type::I8, Value::Integer(n)) => i8::try_from(*n).is_ok(),
+            (Type::I16, Value::Integer(n)) => i16::try_from(*n).is_ok(),
+            (Type::U8, Value::Integer(n)) => u8::try_from(*n).is_ok(),
+            (Type::U16, Value::Integer(n)) => u16::try_from(*n).is_ok(),
+            (Type::U32 | Type::U64, Value::Integer(n)) => *n >= 0,
+            (Type::I32 | Type::I64, Value::Integer(_)) => true,
+            (Type::F32 | Type::F64, Value::Float(_)) => true,
+            (Type::Bool, Value::Boolean(_)) => true,
+            (Type::String, Value::String(_)) => true,
+            (Type::Shared(inner), Value::Shared(shared)) => shared.0.borrow().has_type(inner),
+            (Type::Shared(inner), Value::Weak(weak)) => {
+                weak.get().is_none_or(|value| value.has_type(inner))
+            }
+            (Type::Unique(inner) | Type::Shared(inner), value) => value.has_type(inner),
+            (Type::Vec(element) | Type::Rest(element), Value::Vector(items)) => {
+                **element == Type::Dynamic || items.iter().all(|item| item.has_type(element))
+            }
+            (Type::HashMap(key, value), Value::HashMap(map)) => {
+                Value::String(String::new()).has_type(key)
+                    && (**value == Type::Dynamic || map.values().all(|item| item.has_type(value)))
+            }
+            (Type::Tuple(types), Value::Tuple(items)) => {
+                types.len() == items.len()
+                    && types.iter().zip(items).all(|(ty, item)| item.has_type(ty))
+            }
+            (Type::Named(name), Value::EnumVariant { enum_name, .. }) => name == enum_name,
+            _ => false,
+        }
+    }
+
+    // Formats a value as it appears nested inside a collection, quoting strings
+    fn fmt_nested(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{:?}", s),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Vector(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_nested(f)?;
+                }
+                write!(f, "]")
+            }
+            Value::HashMap(map) => {
+                // Sort keys so output is stable across runs
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                write!(f, "{{")?;
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}: ", key)?;
+                    map[key].fmt_nested(f)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Tuple(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_nested(f)?;
+                }
+                if items.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::Unit => write!(f, "()"),
+            Value::Function { .. } => write!(f, "<function>"),
+            Value::Future(_) => write!(f, "<future>"),
+            Value::Channel(_) => write!(f, "<channel>"),
+            Value::Actor(_) => write!(f, "<actor>"),
+            Value::Shared(shared) => write!(f, "{}", shared.0.borrow()),
+            Value::Weak(weak) => match weak.get() {
+                Some(value) => write!(f, "{}", value),
+                None => write!(f, "<dangling>"),
+            },
+            Value::EnumVariant {
+                enum_name,
+                variant,
+                payload,
+            } => {
+                write!(f, "{}::{}", enum_name, variant)?;
+                if let Some(payload) = payload {
+                    write!(f, "(")?;
+                    payload.fmt_nested(f)?;
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Value::Result(Ok(value)) => {
+                write!(f, "Ok(")?;
+                value.fmt_nested(f)?;
+                write!(f, ")")
+            }
+            Value::Result(Err(message)) => write!(f, "Err({:?})", message),
+            Value::Optional(Some(value)) => {
+                write!(f, "some(")?;
+                value.fmt_nested(f)?;
+                write!(f, ")")
+            }
+            Value::Optional(None) => write!(f, "none"),
+        }
+    }
 }
 
 // Environment to store variables and their values
 #[derive(Debug, Clone, PartialEq)]
 pub struct Environment {
-    values: HashMap<String, Value>,
-    parent: Option<Box<Environment>>,
+    values: SymbolMap<Binding>,
+    // Bindings declared with `~`, and those whose value has since been moved
+    // out, with the type to restore when they are assigned again. A moved
+    // binding hides any outer one of the same name.
+    unique: SymbolSet,
+    moved: SymbolMap<Option<Type>>,
+    parent: Option<Rc<RefCell<Environment>>>,
+}
+
+// A variable's value and its declared or inferred type, which assignments
+// must match. Kept together so assigning costs a single lookup.
+#[derive(Debug, Clone, PartialEq)]
+struct Binding {
+    value: Value,
+    ty: Option<Type>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
-            values: HashMap::new(),
+            values: SymbolMap::default(),
+            unique: SymbolSet::default(),
+            moved: SymbolMap::default(),
             parent: None,
         }
     }
 
-    pub fn with_parent(parent: Environment) -> Self {
+    pub fn with_parent(parent: Rc<RefCell<Environment>>) -> Self {
         Environment {
-            values: HashMap::new(),
-            parent: Some(Box::new(parent)),
+            values: SymbolMap::default(),
+            unique: SymbolSet::default(),
+            moved: SymbolMap::default(),
+            parent: Some(parent),
         }
     }
 
-    pub fn define(&mut self, name: String, value: Value) {
-        self.values.insert(name, value);
+    // Functions defined in a scope capture the scope itself, so once it
+    // exits the two keep each other alive. If nothing else still refers to
+    // `scope`, its bindings are dropped to break those cycles.
+    pub fn release(scope: Rc<RefCell<Environment>>) {
+        if Rc::strong_count(&scope) == 1 {
+            return;
+        }
+        let captures: usize = (scope.borrow().values.values())
+            .map(|binding| binding.value.captures(&scope))
+            .sum();
+        if Rc::strong_count(&scope) == captures + 1 {
+            let values = std::mem::take(&mut scope.borrow_mut().values);
+            drop(values);
+        }
     }
 
-    pub fn get(&self, name: &str) -> Option<Value> {
-        match self.values.get(name) {
-            Some(value) => Some(value.clone()),
-            None => self.parent.as_ref().and_then(|parent| parent.get(name)),
+    // Drops every binding in this scope
+    fn clear(&mut self) {
+        let values = std::mem::take(&mut self.values);
+        self.moved.clear();
+        drop(values);
+    }
+
+    pub fn define(&mut self, name: Symbol, value: Value) {
+        self.values.insert(name, Binding { value, ty: None });
+        if !self.unique.is_empty() {
+            self.unique.remove(&name);
+            self.moved.remove(&name);
         }
     }
-}
 
-// Memory management for heap allocated values
-pub struct Heap {
-    objects: Vec<Value>,
-}
+    // Defines a `~` binding, whose value moves out when passed on
+    pub fn define_unique(&mut self, name: Symbol, value: Value) {
+        self.define(name, value);
+        self.unique.insert(name);
+    }
 
-impl Heap {
-    pub fn new() -> Self {
-        Heap {
-            objects: Vec::new(),
+    // Restricts later assignments to `name` in this scope to values of `ty`
+    pub fn set_type(&mut self, name: Symbol, ty: Type) {
+        if let Some(binding) = self.values.get_mut(&name) {
+            binding.ty = Some(ty);
+        }
+    }
+
+    // Updates an existing binding in the nearest scope that has it. Returns
+    // false if no scope defines `name`. Assigning to a moved binding gives it
+    // a value again, and assigning to a shared one updates the shared value.
+    // Errors if the value doesn't match the binding's type.
+    pub fn assign(&mut self, name: Symbol, value: Value) -> Result<bool, String> {
+        match self.values.get_mut(&name) {
+            Some(binding) => {
+                check_type(name, binding.ty.as_ref(), &value)?;
+                match &mut binding.value {
+                    // Every holder of a shared value sees the update
+                    Value::Shared(shared) => *shared.0.borrow_mut() = value,
+                    slot => *slot = value,
+                }
+                Ok(true)
+            }
+            None if !self.moved.is_empty() && self.moved.contains_key(&name) => {
+                check_type(name, self.moved[&name].as_ref(), &value)?;
+                let ty = self.moved.remove(&name).flatten();
+                self.values.insert(name, Binding { value, ty });
+                Ok(true)
+            }
+            None => match &self.parent {
+                Some(parent) => parent.borrow_mut().assign(name, value),
+                None => Ok(false),
+            },
+        }
+    }
+
+    pub fn get(&self, name: Symbol) -> Option<Value> {
+        self.with_value(name, Value::clone)
+    }
+
+    // Like `get`, but a `~` binding gives up its value and becomes moved
+    pub fn take(&mut self, name: Symbol) -> Option<Value> {
+        if !self.unique.is_empty() && self.unique.contains(&name) {
+            let binding = self.values.remove(&name)?;
+            self.moved.insert(name, binding.ty);
+            return Some(binding.value);
+        }
+        match self.values.get(&name) {
+            Some(binding) => Some(binding.value.clone()),
+            None if self.is_moved_here(name) => None,
+            None => self
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.borrow_mut().take(name)),
         }
     }
 
-    pub fn allocate(&mut self, value: Value) -> usize {
-        let address = self.objects.len();
-        self.objects.push(value);
-        address
+    // Most scopes never move anything, so skip hashing for them
+    fn is_moved_here(&self, name: Symbol) -> bool {
+        !self.moved.is_empty() && self.moved.contains_key(&name)
     }
 
-    pub fn get(&self, address: usize) -> Option<&Value> {
-        self.objects.get(address)
+    // Whether `name` refers to a `~` binding whose value was moved out
+    pub fn is_moved(&self, name: Symbol) -> bool {
+        if self.values.contains_key(&name) {
+            return false;
+        }
+        match &self.parent {
+            _ if self.moved.contains_key(&name) => true,
+            Some(parent) => parent.borrow().is_moved(name),
+            None => false,
+        }
     }
+
+    // All names visible from this scope, including those in parent scopes.
+    // Moved bindings count, so using one is reported as a move error.
+    pub fn names(&self) -> Vec<Symbol> {
+        let mut names: Vec<Symbol> = self
+            .values
+            .keys()
+            .chain(self.moved.keys())
+            .copied()
+            .collect();
+        if let Some(parent) = &self.parent {
+            names.extend(parent.borrow().names());
+        }
+        names
+    }
+
+    // The values defined directly in this scope, with their declared types
+    pub fn bindings(&self) -> impl Iterator<Item = (Symbol, &Value, Option<&Type>)> {
+        self.values
+            .iter()
+            .map(|(name, binding)| (*name, &binding.value, binding.ty.as_ref()))
+    }
+
+    // Runs `f` on a variable without cloning it
+    pub fn with_value<R>(&self, name: Symbol, f: impl FnOnce(&Value) -> R) -> Option<R> {
+        match self.values.get(&name) {
+            Some(binding) => Some(f(&binding.value)),
+            None if self.is_moved_here(name) => None,
+            None => self
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.borrow().with_value(name, f)),
+        }
+    }
+
+    // Runs `f` on a mutable reference to a variable and its type, in
+    // whichever scope holds it
+    pub fn with_value_mut<R>(
+        &mut self,
+        name: Symbol,
+        f: impl FnOnce(&mut Value, Option<&Type>) -> R,
+    ) -> Option<R> {
+        match self.values.get_mut(&name) {
+            Some(binding) => Some(f(&mut binding.value, binding.ty.as_ref())),
+            None if !self.moved.is_empty() && self.moved.contains_key(&name) => None,
+            None => self
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.borrow_mut().with_value_mut(name, f)),
+        }
+    }
+}
+
+#[inline]
+fn check_type(name: Symbol, ty: Option<&Type>, value: &Value) -> Result<(), String> {
+    match ty {
+        Some(ty) if !value.has_type(ty) => Err(type_mismatch(name, ty, value)),
+        _ => Ok(()),
+    }
+}
+
+fn type_mismatch(name: impl fmt::Display, ty: &Type, value: &Value) -> String {
+    match (ty, value) {
+        (
+            Type::I8 | Type::I16 | Type::U8 | Type::U16 | Type::U32 | Type::U64,
+            Value::Integer(n),
+        ) => format!("Integer {} out of range for {} of type {}", n, name, ty),
+        _ => format!(
+            "Type mismatch: {} has type {}, found {}",
+            name,
+            ty,
+            value.type_name()
+        ),
+    }
+}
+
+// What a call hook is told: a script function being entered with its
+// arguments, or returning
+#[derive(Debug, PartialEq)]
+pub enum CallEvent<'a> {
+    Enter {
+        name: Symbol,
+        args: &'a [Value],
+    },
+    Exit {
+        name: Symbol,
+        result: &'a Result<Value, String>,
+    },
 }
 
+type TraceHook = Box<dyn FnMut(&AstNode)>;
+type CallHook = Box<dyn FnMut(CallEvent)>;
+type PauseHook = Box<dyn FnMut(usize, Vec<(Symbol, Value)>)>;
+
+// Default cap on nested user function calls, kept well below the point where
+// the interpreter's own recursion would exhaust a typical 8MB native stack
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
 pub struct Interpreter {
-    environment: Environment,
-    heap: Heap,
+    environment: Rc<RefCell<Environment>>,
+    call_depth: usize,
+    max_call_depth: usize,
+    // Loop iterations and calls left before execution stops, if limited
+    steps_left: Option<usize>,
+    // Whether builtins that touch the filesystem are refused
+    sandboxed: bool,
+    // Observers for debuggers and profilers; see set_trace and set_call_hook
+    trace: Option<TraceHook>,
+    call_hook: Option<CallHook>,
+    // Source lines to pause on, and what to call when execution reaches one
+    breakpoints: HashSet<usize>,
+    pause: Option<PauseHook>,
+    // Calls, total time and frames currently running per script function,
+    // while profiling
+    profile: Option<SymbolMap<(u64, Duration, usize)>>,
+    constant_folding: bool,
+    bytecode: bool,
+    io: Io,
+    // Every actor spawned so far, in spawn order, for delivering messages
+    actors: Vec<Actor>,
+    // Variants of every enum declared so far, by enum name
+    enums: SymbolMap<Vec<Variant>>,
+    // Type aliases declared so far, expanded, for parsing later code
+    aliases: SymbolMap<Type>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The global scope exits with the interpreter. Closures defined in other
+// scopes may still refer to it through their parents, so its bindings are
+// dropped whatever refers to it.
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        self.environment.borrow_mut().clear();
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Self::with_io(Io::default())
+    }
+
+    // Sends everything the output builtins print to `output` instead of stdout
+    pub fn with_writer(output: impl Write + 'static) -> Self {
+        Self::with_io(Io::with_output(output))
+    }
+
+    // Runs IO builtins against `io` instead of the process's stdin and stdout
+    pub fn with_io(io: Io) -> Self {
         Interpreter {
-            environment: Environment::new(),
-            heap: Heap::new(),
+            environment: Rc::new(RefCell::new(Environment::new())),
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            steps_left: None,
+            sandboxed: false,
+            trace: None,
+            call_hook: None,
+            breakpoints: HashSet::new(),
+            pause: None,
+            profile: None,
+            constant_folding: true,
+            bytecode: false,
+            io,
+            actors: Vec::new(),
+            enums: SymbolMap::default(),
+            aliases: SymbolMap::default(),
+        }
+    }
+
+    // Forgets every variable, function, enum, alias and actor defined so far,
+    // keeping the settings and IO. Builtins aren't stored in the
+    // environment, so they stay available.
+    pub fn reset(&mut self) {
+        self.environment.borrow_mut().clear();
+        self.environment = Rc::new(RefCell::new(Environment::new()));
+        self.call_depth = 0;
+        self.actors.clear();
+        self.enums.clear();
+        self.aliases.clear();
+    }
+
+    pub fn defined_names(&self) -> Vec<Symbol> {
+        self.environment.borrow().names()
+    }
+
+    // Every variable and function in the current scope with its declared
+    // type, sorted by name
+    pub fn bindings(&self) -> Vec<(Symbol, Value, Option<Type>)> {
+        let mut bindings: Vec<_> = self
+            .environment
+            .borrow()
+            .bindings()
+            .map(|(name, value, ty)| (name, value.clone(), ty.cloned()))
+            .collect();
+        bindings.sort_by_key(|(name, _, _)| name.as_str());
+        bindings
+    }
+
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    // Limits how many more loop iterations and function calls may run, so
+    // a runaway script errors instead of hanging. The budget is shared by
+    // everything run afterwards; None lifts the limit.
+    pub fn set_max_steps(&mut self, steps: Option<usize>) {
+        self.steps_left = steps;
+    }
+
+    // In sandbox mode, scripts can't read or change the filesystem; every
+    // other builtin still works
+    pub fn set_sandbox(&mut self, enabled: bool) {
+        self.sandboxed = enabled;
+    }
+
+    // Calls `trace` with every node before it's evaluated. Loops run by the
+    // bytecode VM only report the nodes it hands back to the tree-walker.
+    pub fn set_trace(&mut self, trace: impl FnMut(&AstNode) + 'static) {
+        self.trace = Some(Box::new(trace));
+    }
+
+    // Calls `hook` as each script function is entered and as it returns
+    pub fn set_call_hook(&mut self, hook: impl FnMut(CallEvent) + 'static) {
+        self.call_hook = Some(Box::new(hook));
+    }
+
+    // Calls `pause` with the line and the current scope's variables whenever
+    // execution reaches the first statement on one of `lines`. Only code
+    // prepared after this call is marked, so set breakpoints before `eval`.
+    pub fn set_breakpoints(
+        &mut self,
+        lines: impl IntoIterator<Item = usize>,
+        pause: impl FnMut(usize, Vec<(Symbol, Value)>) + 'static,
+    ) {
+        self.breakpoints = lines.into_iter().collect();
+        self.pause = Some(Box::new(pause));
+    }
+
+    // Starts or stops counting calls to each script function and the time
+    // spent in them. Turning profiling on clears earlier counts.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(SymbolMap::default);
+    }
+
+    // Each profiled function with its call count and total time, most
+    // called first. A call's time includes the functions it calls, but a
+    // recursive call's time is only counted once, in its outermost frame.
+    pub fn profile_report(&self) -> Vec<(String, u64, Duration)> {
+        let mut report: Vec<_> = self
+            .profile
+            .iter()
+            .flatten()
+            .map(|(name, (calls, time, _))| (name.to_string(), *calls, *time))
+            .collect();
+        report.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        report
+    }
+
+    // Starts timing a call to `name` if profiling and no other call to it is
+    // running, so recursion isn't timed twice
+    fn profile_enter(&mut self, name: Symbol) -> Option<Instant> {
+        let (_, _, running) = self.profile.as_mut()?.entry(name).or_default();
+        *running += 1;
+        (*running == 1).then(Instant::now)
+    }
+
+    fn profile_exit(&mut self, name: Symbol, start: Option<Instant>) {
+        if let Some(profile) = &mut self.profile {
+            let (calls, time, running) = profile.entry(name).or_default();
+            *calls += 1;
+            *running = running.saturating_sub(1);
+            if let Some(start) = start {
+                *time += start.elapsed();
+            }
+        }
+    }
+
+    // Counts one loop iteration or call against the step budget
+    pub(crate) fn take_step(&mut self) -> Result<(), String> {
+        match &mut self.steps_left {
+            Some(0) => Err("Execution budget exceeded".to_string()),
+            Some(left) => {
+                *left -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Constant folding is on by default; turning it off runs the AST exactly
+    // as parsed
+    pub fn set_constant_folding(&mut self, enabled: bool) {
+        self.constant_folding = enabled;
+    }
+
+    // Compiles while loops to bytecode for the stack VM instead of walking
+    // the AST. This was the default when loop bodies were cloned on every
+    // iteration; since they're borrowed, the tree-walker is the faster of the
+    // two (see the loops benchmark), so the VM is opt-in.
+    pub fn set_bytecode(&mut self, enabled: bool) {
+        self.bytecode = enabled;
+    }
+
+    // Parses `source` and checks its names against what's already defined,
+    // without running it. Only lexer errors have a position.
+    pub fn prepare(&self, source: &str) -> Result<Vec<AstNode>, Diagnostic> {
+        let ast = crate::parser_for(source, &self.breakpoints)?
+            .with_aliases(self.aliases.clone())
+            .parse_recovering()
+            .map_err(|errors| (errors.join("\n"), None))?;
+        Resolver::new(self.defined_names())
+            .with_enums(&self.enums)
+            .resolve(&ast)
+            .map_err(|message| (message, None))?;
+        if self.constant_folding {
+            Ok(fold_constants(ast))
+        } else {
+            Ok(ast)
+        }
+    }
+
+    // Defines every top-level function up front so statements can call
+    // functions declared later in the program, including mutually recursive ones
+    pub fn hoist_functions(&mut self, ast: &[AstNode]) {
+        for node in ast {
+            if let AstNode::FunctionDecl {
+                name,
+                params,
+                defaults,
+                body,
+                is_async,
+                attributes,
+                ..
+            } = node
+            {
+                let func_value = self.make_function(params, defaults, body, *is_async, attributes);
+                self.environment.borrow_mut().define(*name, func_value);
+            }
+        }
+    }
+
+    // A function value closing over the current scope
+    fn make_function(
+        &self,
+        params: &[Param],
+        defaults: &[AstNode],
+        body: &AstNode,
+        is_async: bool,
+        attributes: &[Attribute],
+    ) -> Value {
+        Value::Function {
+            params: params.to_vec(),
+            defaults: defaults.into(),
+            body: Rc::new(body.clone()),
+            closure: Closure(self.environment.clone()),
+            is_async,
+            attributes: attributes.to_vec(),
+        }
+    }
+
+    // Calls `main` if the program defined one. Scripts run it once after all
+    // top-level statements; `eval` never does, so embedders opt in here. An
+    // async `main` is awaited.
+    pub fn run_main(&mut self) -> Result<Value, String> {
+        let has_main = self
+            .environment
+            .borrow()
+            .with_value(Symbol::intern("main"), |value| {
+                matches!(value, Value::Function { .. })
+            })
+            .unwrap_or(false);
+        if has_main {
+            match self.call("main", vec![])? {
+                Value::Future(future) => self.await_future(&future),
+                value => Ok(value),
+            }
+        } else {
+            Ok(Value::Unit)
+        }
+    }
+
+    // Runs `source`, returning the value of its last statement
+    pub fn eval(&mut self, source: &str) -> Result<Value, String> {
+        let ast = self.prepare(source).map_err(|(message, _)| message)?;
+        self.hoist_functions(&ast);
+
+        let mut result = Value::Unit;
+        for node in &ast {
+            result = self.interpret(node)?;
+        }
+        self.deliver_pending()?;
+        Ok(result)
+    }
+
+    // Has actors handle every message still queued, then forgets the ones
+    // nothing can send to any more. Runs at the end of each `eval`.
+    pub fn deliver_pending(&mut self) -> Result<(), String> {
+        while self.deliver_message()? {}
+        self.prune_actors();
+        Ok(())
+    }
+
+    // Drops actors that only this list refers to and that have no mail left
+    fn prune_actors(&mut self) {
+        self.actors
+            .retain(|actor| Rc::strong_count(&actor.0) > 1 || !actor.0.mailbox.is_empty());
+    }
+
+    // Binds a host value as a global variable visible to later scripts
+    pub fn define(&mut self, name: impl Into<Symbol>, value: Value) {
+        self.environment.borrow_mut().define(name.into(), value);
+    }
+
+    // Calls a script function or builtin by name. Builtins are only used
+    // when nothing in scope has that name.
+    pub fn call(&mut self, name: impl Into<Symbol>, args: Vec<Value>) -> Result<Value, String> {
+        self.call_function(name.into(), args, Vec::new())
+    }
+
+    // `call` with keyword arguments, which only script functions accept
+    fn call_function(
+        &mut self,
+        name: Symbol,
+        args: Vec<Value>,
+        named: Vec<(Symbol, Value)>,
+    ) -> Result<Value, String> {
+        let func = self.environment.borrow().get(name);
+        match func {
+            Some(Value::Function {
+                params,
+                body,
+                closure,
+                attributes,
+                ..
+            }) if attributes.contains(&Attribute::Actor) && named.is_empty() => {
+                self.spawn_actor(name, params, body, closure, args)
+            }
+            Some(Value::Function { attributes, .. }) if attributes.contains(&Attribute::Actor) => {
+                Err(format!("Actor {} doesn't take named arguments", name))
+            }
+            Some(Value::Function {
+                params,
+                defaults,
+                body,
+                closure,
+                is_async: true,
+                ..
+            }) => {
+                let args = self.bind_arguments(&params, &defaults, &closure, args, named)?;
+                Self::check_arity(&params, &args)?;
+                Ok(Value::Future(Future(Rc::new(RefCell::new(
+                    FutureState::Pending {
+                        name,
+                        params,
+                        body,
+                        closure,
+                        args,
+                    },
+                )))))
+            }
+            Some(Value::Function {
+                params,
+                defaults,
+                body,
+                closure,
+                ..
+            }) => {
+                let args = self.bind_arguments(&params, &defaults, &closure, args, named)?;
+                self.call_user_function(name, params, &body, args, closure)
+            }
+            Some(_) => Err(format!("{} is not a function", name)),
+            None if StdLib::is_builtin(name.as_str()) && !named.is_empty() => {
+                Err(format!("Builtin {} doesn't take named arguments", name))
+            }
+            None if self.sandboxed
+                && StdLib::capability(name.as_str()) == Some(Capability::Filesystem) =>
+            {
+                Err(format!("Operation not permitted in sandbox mode: {}", name))
+            }
+            None if StdLib::is_builtin(name.as_str()) => {
+                StdLib::handle_builtin_function(name.as_str(), args, &mut self.io)
+            }
+            None => Err(format!("Undefined function: {}", name)),
         }
     }
 
-    pub fn interpret(&mut self, node: AstNode) -> Result<Value, String> {
+    // Nodes are evaluated by reference so loop and function bodies can run
+    // repeatedly without being cloned
+    pub fn interpret(&mut self, node: &AstNode) -> Result<Value, String> {
+        if let Some(trace) = &mut self.trace {
+            trace(node);
+        }
         match node {
-            AstNode::Integer(n) => Ok(Value::Integer(n)),
-            AstNode::Float(f) => Ok(Value::Float(f)),
-            AstNode::String(s) => Ok(Value::String(s)),
-            AstNode::Boolean(b) => Ok(Value::Boolean(b)),
+            AstNode::Integer(n) => Ok(Value::Integer(*n)),
+            AstNode::Float(f) => Ok(Value::Float(*f)),
+            AstNode::String(s) => Ok(Value::String(s.clone())),
+            AstNode::Boolean(b) => Ok(Value::Boolean(*b)),
+            AstNode::Interpolation(parts) => {
+                let mut string = String::new();
+                for part in parts {
+                    match self.interpret(part)? {
+                        Value::String(text) => string.push_str(&text),
+                        value => string.push_str(&value.to_string()),
+                    }
+                }
+                Ok(Value::String(string))
+            }
 
             AstNode::VariableDecl {
-                name, initializer, ..
+                name,
+                type_annotation,
+                initializer,
+                ownership,
             } => {
-                let value = match initializer {
-                    Some(expr) => self.interpret(*expr)?,
-                    None => Value::Unit,
+                let value = match (ownership, initializer.as_deref()) {
+                    // `@` and `#weak` bindings take the reference itself
+                    // rather than the value it points at
+                    (
+                        Some(Ownership::Shared | Ownership::Weak),
+                        Some(AstNode::Identifier(source)),
+                    ) => {
+                        let value = self.environment.borrow().get(*source);
+                        value.ok_or_else(|| self.undefined_variable(*source))?
+                    }
+                    (_, Some(expr)) => self.interpret_moving(expr)?,
+                    (_, None) => Value::Unit,
+                };
+                let value = match (ownership, value) {
+                    // Binding an existing shared value shares it rather than
+                    // copying it
+                    (Some(Ownership::Shared), value @ Value::Shared(_)) => value,
+                    (Some(Ownership::Shared), value) => Value::Shared(Shared::new(value)),
+                    (Some(Ownership::Weak), Value::Shared(shared)) => {
+                        Value::Weak(shared.downgrade())
+                    }
+                    (Some(Ownership::Weak), value @ Value::Weak(_)) => value,
+                    (Some(Ownership::Weak), value) => {
+                        return Err(format!(
+                            "#weak binding {} needs a shared (@) value, found {}",
+                            name,
+                            value.type_name()
+                        ))
+                    }
+                    (_, value) => value,
                 };
-                self.environment.define(name, value.clone());
+                let unique = *ownership == Some(Ownership::Unique);
+                self.declare(*name, value.clone(), type_annotation.as_ref(), unique)?;
                 Ok(value)
             }
 
+            AstNode::DestructureDecl {
+                names,
+                type_annotation,
+                initializer,
+            } => {
+                let value = self.interpret(initializer)?;
+                self.destructure(names, type_annotation.as_ref(), &value)?;
+                Ok(value)
+            }
+
+            AstNode::Tuple(elements) => {
+                let values = elements
+                    .iter()
+                    .map(|element| self.interpret(element))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Tuple(values))
+            }
+
+            AstNode::TupleAccess { target, index } => match self.interpret(target)? {
+                Value::Tuple(items) => items
+                    .get(*index)
+                    .cloned()
+                    .ok_or_else(|| format!("Tuple index {} out of bounds", index)),
+                _ => Err("Field access on a non-tuple value".to_string()),
+            },
+
             AstNode::IndexAccess { target, index } => {
-                let target_val = self.interpret(*target)?;
-                let index_val = self.interpret(*index)?;
+                let index_val = self.interpret(index)?;
 
-                match (target_val, index_val) {
-                    (Value::Vector(vec), Value::Integer(i)) => {
-                        if i < 0 || i as usize >= vec.len() {
-                            return Err("Index out of bounds".to_string());
-                        }
-                        Ok(vec[i as usize].clone())
-                    }
-                    (Value::HashMap(map), key) => {
-                        if let Value::String(key) = key {
-                            match map.get(&key) {
-                                Some(value) => Ok(value.clone()),
-                                None => Err(format!("Key not found: {}", key)),
-                            }
-                        } else {
-                            Err("Key must be a string".to_string())
-                        }
-                    }
-                    _ => Err("Invalid index access".to_string()),
+                // Index named collections by reference to avoid cloning them
+                if let AstNode::Identifier(name) = target.as_ref() {
+                    let result = self
+                        .environment
+                        .borrow()
+                        .with_value(*name, |target| Self::index_value(target, index_val));
+                    return result.ok_or_else(|| self.undefined_variable(*name))?;
                 }
+
+                let target_val = self.interpret(target)?;
+                Self::index_value(&target_val, index_val)
             }
 
-            AstNode::Identifier(name) => self
-                .environment
-                .get(&name)
-                .ok_or(format!("Undefined variable: {}", name)),
+            AstNode::Identifier(name) => self.lookup(*name),
+
+            AstNode::BinaryOp {
+                left,
+                operator: operator @ (Operator::And | Operator::Or),
+                right,
+            } => {
+                // Skip the right operand once the left one decides the result
+                let left_val = self.interpret(left)?;
+                match (operator, &left_val) {
+                    (Operator::And, Value::Boolean(false)) => Ok(Value::Boolean(false)),
+                    (Operator::Or, Value::Boolean(true)) => Ok(Value::Boolean(true)),
+                    (_, Value::Boolean(_)) => {
+                        let right_val = self.interpret(right)?;
+                        self.evaluate_binary_op(operator.clone(), left_val, right_val)
+                    }
+                    _ => Err("Invalid operator for types".to_string()),
+                }
+            }
 
             AstNode::BinaryOp {
                 left,
                 operator,
                 right,
             } => {
-                let left_val = self.interpret(*left)?;
-                let right_val = self.interpret(*right)?;
-                self.evaluate_binary_op(operator, left_val, right_val)
+                let left_val = self.interpret(left)?;
+                let right_val = self.interpret(right)?;
+                self.evaluate_binary_op(operator.clone(), left_val, right_val)
             }
 
             AstNode::CompoundAssign {
@@ -156,69 +1220,73 @@ impl Interpreter {
                 value,
             } => match operator {
                 Operator::Assign => {
-                    if let AstNode::Identifier(name) = *target {
-                        let new_val = self.interpret(*value)?;
-                        self.environment.define(name, new_val.clone());
+                    if let AstNode::Identifier(name) = target.as_ref() {
+                        let new_val = self.interpret_moving(value)?;
+                        self.assign_variable(*name, new_val.clone())?;
                         Ok(new_val)
                     } else {
                         Err("Left side of = must be a variable".to_string())
                     }
                 }
                 Operator::SelfAdd => {
-                    if let AstNode::Identifier(name) = *target {
-                        let curr_val = self
-                            .environment
-                            .get(&name)
-                            .ok_or(format!("Undefined variable: {}", name))?;
-                        let new_val = self.interpret(*value)?;
+                    if let AstNode::Identifier(name) = target.as_ref() {
+                        let curr_val = self.lookup(*name)?;
+                        let new_val = self.interpret(value)?;
                         let result =
                             self.evaluate_binary_op(Operator::Add, curr_val.clone(), new_val)?;
-                        self.environment.define(name, result.clone());
+                        self.assign_variable(*name, result.clone())?;
                         Ok(result)
                     } else {
                         Err("Left side of += must be a variable".to_string())
                     }
                 }
                 Operator::Inc => {
-                    if let AstNode::Identifier(name) = *target {
-                        let curr_val = self
-                            .environment
-                            .get(&name)
-                            .ok_or(format!("Undefined variable: {}", name))?;
+                    if let AstNode::Identifier(name) = target.as_ref() {
+                        let curr_val = self.lookup(*name)?;
                         let new_val = Value::Integer(1);
                         let result =
                             self.evaluate_binary_op(Operator::Add, curr_val.clone(), new_val)?;
-                        self.environment.define(name, result.clone());
+                        self.assign_variable(*name, result.clone())?;
                         Ok(result)
                     } else {
                         Err("Left side of ++ must be a variable".to_string())
                     }
                 }
                 Operator::SelfSub => {
-                    if let AstNode::Identifier(name) = *target {
-                        let curr_val = self
-                            .environment
-                            .get(&name)
-                            .ok_or(format!("Undefined variable: {}", name))?;
-                        let new_val = self.interpret(*value)?;
+                    if let AstNode::Identifier(name) = target.as_ref() {
+                        let curr_val = self.lookup(*name)?;
+                        let new_val = self.interpret(value)?;
                         let result =
                             self.evaluate_binary_op(Operator::Sub, curr_val.clone(), new_val)?;
-                        self.environment.define(name, result.clone());
+                        self.assign_variable(*name, result.clone())?;
                         Ok(result)
                     } else {
                         Err("Left side of -= must be a variable".to_string())
                     }
                 }
+                Operator::SelfMul | Operator::SelfDiv | Operator::SelfMod => {
+                    let (base_op, symbol) = match operator {
+                        Operator::SelfMul => (Operator::Mul, "*="),
+                        Operator::SelfDiv => (Operator::Div, "/="),
+                        _ => (Operator::Mod, "%="),
+                    };
+                    if let AstNode::Identifier(name) = target.as_ref() {
+                        let curr_val = self.lookup(*name)?;
+                        let new_val = self.interpret(value)?;
+                        let result = self.evaluate_binary_op(base_op, curr_val, new_val)?;
+                        self.assign_variable(*name, result.clone())?;
+                        Ok(result)
+                    } else {
+                        Err(format!("Left side of {} must be a variable", symbol))
+                    }
+                }
                 Operator::Dec => {
-                    if let AstNode::Identifier(name) = *target {
-                        let curr_val = self
-                            .environment
-                            .get(&name)
-                            .ok_or(format!("Undefined variable: {}", name))?;
+                    if let AstNode::Identifier(name) = target.as_ref() {
+                        let curr_val = self.lookup(*name)?;
                         let new_val = Value::Integer(1);
                         let result =
                             self.evaluate_binary_op(Operator::Sub, curr_val.clone(), new_val)?;
-                        self.environment.define(name, result.clone());
+                        self.assign_variable(*name, result.clone())?;
                         Ok(result)
                     } else {
                         Err("Left side of -- must be a variable".to_string())
@@ -231,14 +1299,11 @@ impl Interpreter {
                 operator: UnaryOperator::Inc,
                 operand,
             } => {
-                if let AstNode::Identifier(name) = *operand {
-                    let curr_val = self
-                        .environment
-                        .get(&name)
-                        .ok_or(format!("Undefined variable: {}", name))?;
+                if let AstNode::Identifier(name) = operand.as_ref() {
+                    let curr_val = self.lookup(*name)?;
                     let one = Value::Integer(1);
                     let result = self.evaluate_binary_op(Operator::Add, curr_val.clone(), one)?;
-                    self.environment.define(name, result.clone());
+                    self.assign_variable(*name, result.clone())?;
                     Ok(result)
                 } else {
                     Err("Operand of ++ must be a variable".to_string())
@@ -249,14 +1314,11 @@ impl Interpreter {
                 operator: UnaryOperator::Dec,
                 operand,
             } => {
-                if let AstNode::Identifier(name) = *operand {
-                    let curr_val = self
-                        .environment
-                        .get(&name)
-                        .ok_or(format!("Undefined variable: {}", name))?;
+                if let AstNode::Identifier(name) = operand.as_ref() {
+                    let curr_val = self.lookup(*name)?;
                     let one = Value::Integer(1);
                     let result = self.evaluate_binary_op(Operator::Sub, curr_val.clone(), one)?;
-                    self.environment.define(name, result.clone());
+                    self.assign_variable(*name, result.clone())?;
                     Ok(result)
                 } else {
                     Err("Operand of -- must be a variable".to_string())
@@ -264,15 +1326,21 @@ impl Interpreter {
             }
 
             AstNode::UnaryOp { operator, operand } => {
-                let val = self.interpret(*operand)?;
-                self.evaluate_unary_op(operator, val)
+                let val = self.interpret(operand)?;
+                self.evaluate_unary_op(operator.clone(), val)
             }
 
-            AstNode::Block(statements) => {
+            AstNode::Block {
+                statements,
+                discards_value,
+            } => {
                 let mut result = Value::Unit;
                 for stmt in statements {
                     result = self.interpret(stmt)?;
                 }
+                if *discards_value {
+                    result = Value::Unit;
+                }
                 Ok(result)
             }
 
@@ -281,76 +1349,277 @@ impl Interpreter {
                 then_branch,
                 else_branch,
             } => {
-                let cond_val = self.interpret(*condition)?;
+                let cond_val = self.interpret(condition)?;
                 match cond_val {
-                    Value::Boolean(true) => self.interpret(*then_branch),
+                    Value::Boolean(true) => self.interpret(then_branch),
                     Value::Boolean(false) => {
                         if let Some(else_branch) = else_branch {
-                            self.interpret(*else_branch)
+                            self.interpret(else_branch)
                         } else {
                             Ok(Value::Unit)
                         }
                     }
-                    _ => Err("Condition must be a boolean".to_string()),
+                    other => Err(Self::condition_error(&other)),
+                }
+            }
+
+            AstNode::WhileLoop { .. } if self.bytecode => {
+                let code = vm::compile(node);
+                vm::run(self, &code)
+            }
+
+            AstNode::WhileLoop { condition, body } => {
+                loop {
+                    let cond_val = self.interpret(condition)?;
+                    match cond_val {
+                        Value::Boolean(true) => {
+                            self.take_step()?;
+                            self.interpret(body)?;
+                        }
+                        Value::Boolean(false) => break,
+                        other => return Err(Self::condition_error(&other)),
+                    }
+                }
+                Ok(Value::Unit)
+            }
+
+            AstNode::DoWhile { body, condition } => {
+                loop {
+                    self.take_step()?;
+                    self.interpret(body)?;
+                    match self.interpret(condition)? {
+                        Value::Boolean(true) => {}
+                        Value::Boolean(false) => break,
+                        other => return Err(Self::condition_error(&other)),
+                    }
+                }
+                Ok(Value::Unit)
+            }
+
+            AstNode::Match { scrutinee, arms } => {
+                let value = self.interpret(scrutinee)?;
+                for arm in arms {
+                    let matched = match &arm.pattern {
+                        Pattern::Wildcard => true,
+                        Pattern::Literal(literal) => self.interpret(literal)? == value,
+                        Pattern::Variant {
+                            enum_name,
+                            variant,
+                            binding,
+                        } => match &value {
+                            Value::EnumVariant {
+                                enum_name: name,
+                                variant: tag,
+                                payload,
+                            } if name == enum_name && tag == variant => match (binding, payload) {
+                                (Some(binding), Some(payload)) => {
+                                    return self.interpret_binding(
+                                        &arm.body,
+                                        *binding,
+                                        (**payload).clone(),
+                                    );
+                                }
+                                (Some(_), None) => {
+                                    return Err(format!(
+                                        "{}::{} has no value to bind",
+                                        enum_name, variant
+                                    ));
+                                }
+                                (None, _) => true,
+                            },
+                            _ => false,
+                        },
+                    };
+                    if matched {
+                        return self.interpret(&arm.body);
+                    }
                 }
+                Err(format!("Non-exhaustive match: no arm matched {}", value))
             }
 
-            AstNode::WhileLoop { condition, body } => {
-                loop {
-                    let cond_val = self.interpret(*condition.clone())?;
-                    match cond_val {
-                        Value::Boolean(true) => {
-                            self.interpret(*body.clone())?;
-                        }
-                        Value::Boolean(false) => break,
-                        _ => return Err("Condition must be a boolean".to_string()),
+            AstNode::Try {
+                body,
+                binding,
+                handler,
+            } => {
+                let environment = self.environment.clone();
+                match self.interpret(body) {
+                    Ok(value) => Ok(value),
+                    Err(message) => {
+                        // The error may have left a function's scope current
+                        self.environment = environment;
+                        self.interpret_binding(handler, *binding, Value::String(message))
                     }
                 }
+            }
+
+            AstNode::Breakpoint(line) => {
+                let locals = self
+                    .bindings()
+                    .into_iter()
+                    .map(|(name, value, _)| (name, value))
+                    .collect();
+                if let Some(pause) = &mut self.pause {
+                    pause(*line, locals);
+                }
                 Ok(Value::Unit)
             }
 
             AstNode::FunctionDecl {
-                name, params, body, ..
+                name,
+                params,
+                defaults,
+                body,
+                is_async,
+                attributes,
+                ..
             } => {
-                let func_value = Value::Function {
-                    params,
-                    body: body.clone(),
-                    closure: self.environment.clone(),
-                };
-                self.environment.define(name.clone(), func_value.clone());
-
-                if name == "main" {
-                    return self.call_user_function(
-                        vec![],
-                        *body,
-                        vec![],
-                        self.environment.clone(),
-                    );
+                if attributes.contains(&Attribute::Sync) {
+                    self.check_sync_capture(*name, params, body)?;
                 }
-
+                // The closure shares the scope the function is defined into, so
+                // the body can see its own name and recurse from any nesting
+                let func_value = self.make_function(params, defaults, body, *is_async, attributes);
+                self.environment
+                    .borrow_mut()
+                    .define(*name, func_value.clone());
                 Ok(func_value)
             }
 
+            AstNode::Lambda {
+                params,
+                defaults,
+                body,
+                ..
+            } => Ok(self.make_function(params, defaults, body, false, &[])),
+
+            // Aliases are expanded while parsing
+            AstNode::TypeAlias { name, ty } => {
+                self.aliases.insert(*name, ty.clone());
+                Ok(Value::Unit)
+            }
+
+            AstNode::EnumDecl { name, variants } => {
+                self.enums.insert(*name, variants.clone());
+                Ok(Value::Unit)
+            }
+
+            AstNode::EnumVariant {
+                enum_name,
+                variant,
+                payload,
+            } => {
+                let payload_type = self
+                    .enums
+                    .get(enum_name)
+                    .ok_or_else(|| format!("Undefined enum: {}", enum_name))?
+                    .iter()
+                    .find(|(name, _)| name == variant)
+                    .ok_or_else(|| format!("Enum {} has no variant {}", enum_name, variant))?
+                    .1
+                    .clone();
+                let payload = match (payload_type, payload) {
+                    (None, None) => None,
+                    (Some(ty), Some(payload)) => {
+                        let value = self.interpret_moving(payload)?;
+                        if !value.has_type(&ty) {
+                            let name = format!("{}::{}", enum_name, variant);
+                            return Err(type_mismatch(name, &ty, &value));
+                        }
+                        Some(Box::new(value))
+                    }
+                    (None, Some(_)) => {
+                        return Err(format!("{}::{} doesn't take a value", enum_name, variant))
+                    }
+                    (Some(ty), None) => {
+                        return Err(format!(
+                            "{}::{} needs a value of type {}",
+                            enum_name, variant, ty
+                        ))
+                    }
+                };
+                Ok(Value::EnumVariant {
+                    enum_name: *enum_name,
+                    variant: *variant,
+                    payload,
+                })
+            }
+
+            AstNode::Await { expression } => match self.interpret(expression)? {
+                Value::Future(future) => self.await_future(&future),
+                // Awaiting anything else yields it unchanged
+                value => Ok(value),
+            },
+
             AstNode::FunctionCall { name, args } => {
-                let evaluated_args = args
-                    .into_iter()
-                    .map(|arg| self.interpret(arg))
-                    .collect::<Result<Vec<_>, _>>()?;
+                // Mutating builtins on a named collection update it in place
+                // rather than operating on a clone
+                let is_bound = self
+                    .environment
+                    .borrow()
+                    .with_value(*name, |_| ())
+                    .is_some();
+                if StdLib::is_mutating_builtin(name.as_str()) && !is_bound {
+                    if let Some(AstNode::Identifier(var)) = args.first() {
+                        let rest = args
+                            .iter()
+                            .skip(1)
+                            .map(|arg| self.interpret(arg))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        let result = self
+                            .environment
+                            .borrow_mut()
+                            .with_value_mut(*var, |target, ty| {
+                                mutate_binding(*name, *var, target, ty, rest)
+                            });
+                        return result.ok_or_else(|| self.undefined_variable(*var))?;
+                    }
+                }
 
-                if let Some(func) = self.environment.get(&name) {
-                    match func {
-                        Value::Function {
-                            params,
-                            body,
-                            closure,
-                        } => self.call_user_function(params, *body, evaluated_args, closure),
-                        _ => StdLib::handle_builtin_function(&name, evaluated_args),
+                let mut evaluated_args = Vec::with_capacity(args.len());
+                let mut named = Vec::new();
+                for arg in args {
+                    match arg {
+                        AstNode::NamedArgument { name, value } => {
+                            named.push((*name, self.interpret_moving(value)?))
+                        }
+                        arg => evaluated_args.push(self.interpret_moving(arg)?),
                     }
-                } else {
-                    StdLib::handle_builtin_function(&name, evaluated_args)
                 }
+
+                self.call_function(*name, evaluated_args, named)
+            }
+
+            AstNode::ChannelCreate => Ok(Value::Channel(Channel::default())),
+
+            AstNode::Send { channel, value } => {
+                let target = self.interpret(channel)?;
+                let value = self.interpret(value)?;
+                match target {
+                    Value::Channel(channel) => channel.send(value),
+                    Value::Actor(actor) => actor.0.mailbox.send(value),
+                    other => return Err(format!("Cannot send to {}", other.type_name())),
+                }
+                Ok(Value::Unit)
             }
 
+            AstNode::Receive { channel } => match self.interpret(channel)? {
+                // Actors only run while something waits on a channel, so an
+                // empty channel lets queued messages be handled first
+                Value::Channel(channel) => loop {
+                    if let Some(value) = channel.try_recv() {
+                        return Ok(value);
+                    }
+                    if !self.deliver_message()? {
+                        return Err(
+                            "Receive on an empty channel: no actor has messages left to handle"
+                                .to_string(),
+                        );
+                    }
+                },
+                other => Err(format!("Cannot receive from {}", other.type_name())),
+            },
+
             // Handle unique ownership (~)
             AstNode::Ownership(_ownership) => {
                 // Implementation for ownership handling
@@ -361,56 +1630,455 @@ impl Interpreter {
         }
     }
 
-    fn call_user_function(
+    // Reads a variable, looking through shared and weak bindings to the
+    // value they point at
+    pub(crate) fn lookup(&self, name: Symbol) -> Result<Value, String> {
+        let value = self.environment.borrow().get(name);
+        match value {
+            Some(value) => Self::read_through(name, value),
+            None => Err(self.undefined_variable(name)),
+        }
+    }
+
+    fn read_through(name: Symbol, value: Value) -> Result<Value, String> {
+        match value {
+            Value::Shared(shared) => Ok(shared.get()),
+            Value::Weak(weak) => weak
+                .get()
+                .ok_or_else(|| format!("Weak reference dangling: {}", name)),
+            value => Ok(value),
+        }
+    }
+
+    // Evaluates a value that is being passed on. A bare `~` variable moves
+    // its value, so the variable can't be used afterwards.
+    fn interpret_moving(&mut self, node: &AstNode) -> Result<Value, String> {
+        match node {
+            AstNode::Identifier(name) => self.take_variable(*name),
+            _ => self.interpret(node),
+        }
+    }
+
+    pub(crate) fn take_variable(&mut self, name: Symbol) -> Result<Value, String> {
+        let value = self.environment.borrow_mut().take(name);
+        match value {
+            Some(value) => Self::read_through(name, value),
+            None => Err(self.undefined_variable(name)),
+        }
+    }
+
+    fn undefined_variable(&self, name: Symbol) -> String {
+        if self.environment.borrow().is_moved(name) {
+            format!("Use of moved value: {}", name)
+        } else {
+            format!("Undefined variable: {}", name)
+        }
+    }
+
+    // Binds a `let` in the current scope. The value must match the annotation
+    // if there is one; otherwise the binding takes the type inferred from the
+    // value. Later assignments must match that type either way.
+    pub(crate) fn declare(
         &mut self,
-        params: Vec<(String, Type)>,
-        body: AstNode,
-        args: Vec<Value>,
-        closure: Environment,
-    ) -> Result<Value, String> {
-        if args.len() != params.len() {
-            return Err(format!(
+        name: Symbol,
+        value: Value,
+        annotation: Option<&Type>,
+        unique: bool,
+    ) -> Result<(), String> {
+        let ty = match annotation {
+            Some(ty) if !value.has_type(ty) => return Err(type_mismatch(name, ty, &value)),
+            Some(ty) => Some(ty.clone()),
+            None => value.inferred_type(),
+        };
+        let mut environment = self.environment.borrow_mut();
+        if unique {
+            environment.define_unique(name, value);
+        } else {
+            environment.define(name, value);
+        }
+        if let Some(ty) = ty {
+            environment.set_type(name, ty);
+        }
+        Ok(())
+    }
+
+    // Binds each name to its element of a tuple, with the type of that
+    // element in the annotation if there is one
+    fn destructure(
+        &mut self,
+        names: &[Symbol],
+        annotation: Option<&Type>,
+        value: &Value,
+    ) -> Result<(), String> {
+        let items = match value {
+            Value::Tuple(items) if items.len() == names.len() => items,
+            Value::Tuple(items) => {
+                return Err(format!(
+                    "Cannot destructure a tuple of {} elements into {} names",
+                    items.len(),
+                    names.len()
+                ))
+            }
+            _ => return Err("Only tuples can be destructured".to_string()),
+        };
+        let types = match annotation {
+            Some(Type::Tuple(types)) if types.len() == names.len() => {
+                types.iter().map(Some).collect()
+            }
+            Some(ty) => {
+                return Err(format!(
+                    "Cannot destructure into {} names with type {}",
+                    names.len(),
+                    ty
+                ))
+            }
+            None => vec![None; names.len()],
+        };
+        // Check every element before binding any
+        for ((name, item), ty) in names.iter().zip(items).zip(&types) {
+            if let Some(ty) = ty.filter(|ty| !item.has_type(ty)) {
+                return Err(type_mismatch(name, ty, item));
+            }
+        }
+        for ((name, item), ty) in names.iter().zip(items).zip(types) {
+            self.declare(*name, item.clone(), ty, false)?;
+        }
+        Ok(())
+    }
+
+    // Assignment updates the nearest existing binding, so functions can mutate
+    // variables they captured; unknown names are defined in the current scope
+    pub(crate) fn assign_variable(&mut self, name: Symbol, value: Value) -> Result<(), String> {
+        if !self.environment.borrow_mut().assign(name, value.clone())? {
+            self.environment.borrow_mut().define(name, value);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn condition_error(value: &Value) -> String {
+        format!(
+            "Condition must be a boolean, found {} {}",
+            value.type_name(),
+            value
+        )
+    }
+
+    fn index_value(target: &Value, index: Value) -> Result<Value, String> {
+        match (target, index) {
+            (Value::Shared(shared), index) => Self::index_value(&shared.0.borrow(), index),
+            (Value::Weak(weak), index) => match weak.get() {
+                Some(target) => Self::index_value(&target, index),
+                None => Err("Weak reference dangling".to_string()),
+            },
+            (Value::Vector(vec) | Value::Tuple(vec), Value::Integer(i)) => {
+                StdLib::vector_index(vec.len(), i)
+                    .map(|index| vec[index].clone())
+                    .ok_or_else(|| "Index out of bounds".to_string())
+            }
+            (Value::HashMap(map), key) => {
+                if let Value::String(key) = key {
+                    match map.get(&key) {
+                        Some(value) => Ok(value.clone()),
+                        None => Err(format!("Key not found: {}", key)),
+                    }
+                } else {
+                    Err("Key must be a string".to_string())
+                }
+            }
+            _ => Err("Invalid index access".to_string()),
+        }
+    }
+
+    fn check_arity(params: &[Param], args: &[Value]) -> Result<(), String> {
+        if args.len() == params.len() {
+            Ok(())
+        } else {
+            Err(format!(
                 "Function expected {} arguments but got {}",
                 params.len(),
                 args.len()
+            ))
+        }
+    }
+
+    // Puts named arguments in their parameters' places after the positional
+    // ones, and fills in parameters the call leaves out with their defaults
+    fn bind_arguments(
+        &mut self,
+        params: &[Param],
+        defaults: &[AstNode],
+        closure: &Closure,
+        mut args: Vec<Value>,
+        named: Vec<(Symbol, Value)>,
+    ) -> Result<Vec<Value>, String> {
+        // A rest parameter collects whatever is left after the others
+        if let Some((_, Type::Rest(_))) = params.last() {
+            let fixed = params.len() - 1;
+            let rest = if args.len() > fixed {
+                args.split_off(fixed)
+            } else {
+                Vec::new()
+            };
+            let mut bound =
+                self.bind_arguments(&params[..fixed], defaults, closure, args, named)?;
+            bound.push(Value::Vector(rest));
+            return Ok(bound);
+        }
+        if named.is_empty() && (defaults.is_empty() || args.len() >= params.len()) {
+            return Ok(args);
+        }
+        let required = params.len() - defaults.len();
+        if named.is_empty() && args.len() < required {
+            return Err(format!(
+                "Function expected at least {} arguments but got {}",
+                required,
+                args.len()
             ));
         }
+        if args.len() > params.len() {
+            Self::check_arity(params, &args)?;
+        }
 
-        let mut func_env = Environment::with_parent(closure);
+        let mut slots: Vec<Option<Value>> = args.into_iter().map(Some).collect();
+        slots.resize(params.len(), None);
+        for (name, value) in named {
+            let index = params
+                .iter()
+                .position(|(param, _)| *param == name)
+                .ok_or_else(|| format!("No parameter named {}", name))?;
+            if slots[index].replace(value).is_some() {
+                return Err(format!("Argument {} given more than once", name));
+            }
+        }
 
-        for ((name, _type), value) in params.into_iter().zip(args) {
-            func_env.define(name, value);
+        let mut bound = Vec::with_capacity(params.len());
+        for (index, slot) in slots.into_iter().enumerate() {
+            let value = match slot {
+                Some(value) => value,
+                None if index >= required => {
+                    self.evaluate_default(&defaults[index - required], closure)?
+                }
+                None => return Err(format!("Missing argument for {}", params[index].0)),
+            };
+            bound.push(value);
         }
+        Ok(bound)
+    }
 
-        let previous_env = std::mem::replace(&mut self.environment, func_env);
+    // Runs `body` in a child scope where `name` is bound to `value`
+    fn interpret_binding(
+        &mut self,
+        body: &AstNode,
+        name: Symbol,
+        value: Value,
+    ) -> Result<Value, String> {
+        let mut scope = Environment::with_parent(self.environment.clone());
+        scope.define(name, value);
+        let previous_env = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(scope)));
         let result = self.interpret(body);
         self.environment = previous_env;
+        result
+    }
+
+    // Defaults are evaluated on every call, in the scope the function was
+    // defined in
+    fn evaluate_default(&mut self, default: &AstNode, closure: &Closure) -> Result<Value, String> {
+        let previous_env = std::mem::replace(&mut self.environment, closure.0.clone());
+        let result = self.interpret(default);
+        self.environment = previous_env;
+        result
+    }
+
+    // Runs a pending future's call and caches the result, so awaiting it
+    // again returns the same value without rerunning the body
+    fn await_future(&mut self, future: &Future) -> Result<Value, String> {
+        let (name, params, body, closure, args) = match &*future.0.borrow() {
+            FutureState::Ready(value) => return Ok(value.clone()),
+            FutureState::Pending {
+                name,
+                params,
+                body,
+                closure,
+                args,
+            } => (
+                *name,
+                params.clone(),
+                body.clone(),
+                closure.clone(),
+                args.clone(),
+            ),
+        };
+        let value = self.call_user_function(name, params, &body, args, closure)?;
+        *future.0.borrow_mut() = FutureState::Ready(value.clone());
+        Ok(value)
+    }
+
+    // Every variable a `#sync` function's body uses from the scope it's
+    // declared in must be safe to share
+    fn check_sync_capture(
+        &self,
+        function: Symbol,
+        params: &[Param],
+        body: &AstNode,
+    ) -> Result<(), String> {
+        let environment = self.environment.borrow();
+        for name in Resolver::free_variables(params, body) {
+            if name != function && environment.with_value(name, Value::is_shareable) == Some(false)
+            {
+                return Err(format!(
+                    "#sync function {} captures {}, which references the local heap",
+                    function, name
+                ));
+            }
+        }
+        Ok(())
+    }
 
+    // Calling an `#actor` function binds every parameter but the last in a
+    // scope of its own; the last receives each message sent to the actor
+    fn spawn_actor(
+        &mut self,
+        name: Symbol,
+        mut params: Vec<Param>,
+        body: Rc<AstNode>,
+        closure: Closure,
+        args: Vec<Value>,
+    ) -> Result<Value, String> {
+        let message = params
+            .pop()
+            .ok_or_else(|| "An actor needs a parameter to receive messages".to_string())?;
+        Self::check_arity(&params, &args)?;
+
+        let mut scope = Environment::with_parent(closure.0);
+        for ((name, _type), value) in params.into_iter().zip(args) {
+            scope.define(name, value);
+        }
+
+        let actor = Actor(Rc::new(ActorState {
+            name,
+            mailbox: Channel::default(),
+            scope: Closure(Rc::new(RefCell::new(scope))),
+            message,
+            body,
+        }));
+        self.prune_actors();
+        self.actors.push(actor.clone());
+        Ok(Value::Actor(actor))
+    }
+
+    // Has the earliest spawned actor with mail handle one message. Returns
+    // false if every mailbox is empty.
+    fn deliver_message(&mut self) -> Result<bool, String> {
+        let Some(actor) = self.actors.iter().find(|actor| !actor.0.mailbox.is_empty()) else {
+            return Ok(false);
+        };
+        let actor = actor.0.clone();
+        if let Some(message) = actor.mailbox.try_recv() {
+            self.call_user_function(
+                actor.name,
+                vec![actor.message.clone()],
+                &actor.body,
+                vec![message],
+                actor.scope.clone(),
+            )?;
+        }
+        Ok(true)
+    }
+
+    fn call_user_function(
+        &mut self,
+        name: Symbol,
+        params: Vec<Param>,
+        body: &AstNode,
+        args: Vec<Value>,
+        closure: Closure,
+    ) -> Result<Value, String> {
+        Self::check_arity(&params, &args)?;
+
+        if self.call_depth >= self.max_call_depth {
+            return Err(format!(
+                "Maximum recursion depth exceeded ({})",
+                self.max_call_depth
+            ));
+        }
+        self.take_step()?;
+        if let Some(hook) = &mut self.call_hook {
+            hook(CallEvent::Enter { name, args: &args });
+        }
+
+        let mut func_env = Environment::with_parent(closure.0);
+
+        for ((param, param_type), value) in params.into_iter().zip(args) {
+            if matches!(param_type, Type::Unique(_)) {
+                func_env.define_unique(param, value);
+            } else {
+                func_env.define(param, value);
+            }
+        }
+
+        let previous_env =
+            std::mem::replace(&mut self.environment, Rc::new(RefCell::new(func_env)));
+        self.call_depth += 1;
+        let start = self.profile_enter(name);
+        let result = self.interpret(body);
+        self.profile_exit(name, start);
+        self.call_depth -= 1;
+        Environment::release(std::mem::replace(&mut self.environment, previous_env));
+
+        if let Some(hook) = &mut self.call_hook {
+            hook(CallEvent::Exit {
+                name,
+                result: &result,
+            });
+        }
         result
     }
 
-    fn evaluate_binary_op(
+    pub(crate) fn evaluate_binary_op(
         &mut self,
         operator: Operator,
         left: Value,
         right: Value,
     ) -> Result<Value, String> {
         match (operator, left, right) {
-            (Operator::Add, Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a + b)),
-            (Operator::Sub, Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a - b)),
-            (Operator::Mul, Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a * b)),
+            // Checked so overflow is an error rather than a panic or a wrap
+            (Operator::Add, Value::Integer(a), Value::Integer(b)) => checked(a.checked_add(b)),
+            (Operator::Sub, Value::Integer(a), Value::Integer(b)) => checked(a.checked_sub(b)),
+            (Operator::Mul, Value::Integer(a), Value::Integer(b)) => checked(a.checked_mul(b)),
+            // Checked so i32::MIN / -1 is an error rather than a panic
             (Operator::Div, Value::Integer(a), Value::Integer(b)) => {
                 if b == 0 {
                     Err("Division by zero".to_string())
                 } else {
-                    Ok(Value::Integer(a / b))
+                    a.checked_div(b)
+                        .map(Value::Integer)
+                        .ok_or_else(|| "Integer overflow in division".to_string())
                 }
             }
             (Operator::Mod, Value::Integer(a), Value::Integer(b)) => {
                 if b == 0 {
                     Err("Modulus by zero".to_string())
                 } else {
-                    Ok(Value::Integer(a % b))
+                    a.checked_rem(b)
+                        .map(Value::Integer)
+                        .ok_or_else(|| "Integer overflow in modulus".to_string())
+                }
+            }
+            (Operator::Add, Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
+            (Operator::Sub, Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
+            (Operator::Mul, Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
+            (Operator::Div, Value::Float(a), Value::Float(b)) => {
+                if b == 0.0 {
+                    Err("Division by zero".to_string())
+                } else {
+                    Ok(Value::Float(a / b))
+                }
+            }
+            (Operator::Mod, Value::Float(a), Value::Float(b)) => {
+                if b == 0.0 {
+                    Err("Modulus by zero".to_string())
+                } else {
+                    Ok(Value::Float(a % b))
                 }
             }
             (Operator::Eq, Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a == b)),
@@ -419,30 +2087,95 @@ impl Interpreter {
             (Operator::Gt, Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a > b)),
             (Operator::LtEq, Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a <= b)),
             (Operator::GtEq, Value::Integer(a), Value::Integer(b)) => Ok(Value::Boolean(a >= b)),
+            // Collections compare structurally, recursing into nested values
+            (Operator::Eq, Value::Vector(a), Value::Vector(b)) => Ok(Value::Boolean(a == b)),
+            (Operator::NotEq, Value::Vector(a), Value::Vector(b)) => Ok(Value::Boolean(a != b)),
+            (Operator::Eq, Value::HashMap(a), Value::HashMap(b)) => Ok(Value::Boolean(a == b)),
+            (Operator::NotEq, Value::HashMap(a), Value::HashMap(b)) => Ok(Value::Boolean(a != b)),
+            // Enum values are equal when their variants and payloads are
+            (Operator::Eq, a @ Value::EnumVariant { .. }, b @ Value::EnumVariant { .. }) => {
+                Ok(Value::Boolean(a == b))
+            }
+            (Operator::NotEq, a @ Value::EnumVariant { .. }, b @ Value::EnumVariant { .. }) => {
+                Ok(Value::Boolean(a != b))
+            }
+            (Operator::In, item, Value::Vector(vec)) => Ok(Value::Boolean(vec.contains(&item))),
+            (Operator::In, Value::String(key), Value::HashMap(map)) => {
+                Ok(Value::Boolean(map.contains_key(&key)))
+            }
+            (Operator::In, Value::String(needle), Value::String(haystack)) => {
+                Ok(Value::Boolean(haystack.contains(&needle)))
+            }
             (Operator::And, Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(a && b)),
             (Operator::Or, Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(a || b)),
             _ => Err("Invalid operator for types".to_string()),
         }
     }
 
-    fn evaluate_unary_op(
+    pub(crate) fn evaluate_unary_op(
         &mut self,
         operator: UnaryOperator,
         operand: Value,
     ) -> Result<Value, String> {
         match (operator, operand) {
-            (UnaryOperator::Neg, Value::Integer(n)) => Ok(Value::Integer(-n)),
+            (UnaryOperator::Neg, Value::Integer(n)) => checked(n.checked_neg()),
             (UnaryOperator::Not, Value::Boolean(b)) => Ok(Value::Boolean(!b)),
+            (UnaryOperator::BitNot, Value::Integer(n)) => Ok(Value::Integer(!n)),
             _ => Err("Invalid unary operator for type".to_string()),
         }
     }
 }
 
+// Runs the mutating builtin `builtin` on the collection bound to `var`,
+// first checking that the element it adds matches the binding's type. An `@`
+// collection is updated for every binding that shares it.
+fn mutate_binding(
+    builtin: Symbol,
+    var: Symbol,
+    target: &mut Value,
+    ty: Option<&Type>,
+    args: Vec<Value>,
+) -> Result<Value, String> {
+    let builtin = builtin.as_str();
+    if let (Some(element), Some(value)) = (ty.and_then(|ty| element_type(builtin, ty)), args.last())
+    {
+        if !value.has_type(element) {
+            return Err(type_mismatch(format!("element of {}", var), element, value));
+        }
+    }
+    match target {
+        Value::Shared(shared) => StdLib::mutate_in_place(builtin, &mut shared.0.borrow_mut(), args),
+        Value::Weak(weak) => match weak.0.upgrade() {
+            Some(value) => StdLib::mutate_in_place(builtin, &mut value.borrow_mut(), args),
+            None => Err(format!("Weak reference dangling: {}", var)),
+        },
+        target => StdLib::mutate_in_place(builtin, target, args),
+    }
+}
+
+// The type of the element `builtin` adds to a collection of type `ty`, if
+// the type constrains it
+fn element_type<'a>(builtin: &str, ty: &'a Type) -> Option<&'a Type> {
+    match (builtin, ty) {
+        (_, Type::Shared(inner) | Type::Unique(inner)) => element_type(builtin, inner),
+        ("push" | "set", Type::Vec(element)) => Some(element),
+        ("insert", Type::HashMap(_, value)) => Some(value),
+        _ => None,
+    }
+}
+
+// The result of checked integer arithmetic, or an overflow error
+fn checked(result: Option<i32>) -> Result<Value, String> {
+    result
+        .map(Value::Integer)
+        .ok_or_else(|| "Integer overflow".to_string())
+}
+
 // Add tests
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::AstNode;
+    use crate::parser::{AstNode, MatchArm, Type};
 
     #[test]
     fn test_basic_arithmetic() {
@@ -453,6 +2186,322 @@ mod tests {
             right: Box::new(AstNode::Integer(3)),
         };
 
-        assert_eq!(interpreter.interpret(ast).unwrap(), Value::Integer(8));
+        assert_eq!(interpreter.interpret(&ast).unwrap(), Value::Integer(8));
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() {
+        let mut interpreter = Interpreter::new();
+        let failing = || {
+            Box::new(AstNode::FunctionCall {
+                name: "undefined_function".into(),
+                args: vec![],
+            })
+        };
+
+        let and = AstNode::BinaryOp {
+            left: Box::new(AstNode::Boolean(false)),
+            operator: Operator::And,
+            right: failing(),
+        };
+        assert_eq!(interpreter.interpret(&and), Ok(Value::Boolean(false)));
+
+        let or = AstNode::BinaryOp {
+            left: Box::new(AstNode::Boolean(true)),
+            operator: Operator::Or,
+            right: failing(),
+        };
+        assert_eq!(interpreter.interpret(&or), Ok(Value::Boolean(true)));
+
+        let evaluated = AstNode::BinaryOp {
+            left: Box::new(AstNode::Boolean(true)),
+            operator: Operator::And,
+            right: failing(),
+        };
+        assert!(interpreter.interpret(&evaluated).is_err());
+    }
+
+    #[test]
+    fn test_non_boolean_condition_error() {
+        let mut interpreter = Interpreter::new();
+        let if_expr = AstNode::IfExpr {
+            condition: Box::new(AstNode::Integer(5)),
+            then_branch: Box::new(AstNode::Block {
+                statements: vec![],
+                discards_value: false,
+            }),
+            else_branch: None,
+        };
+        assert_eq!(
+            interpreter.interpret(&if_expr),
+            Err("Condition must be a boolean, found integer 5".to_string())
+        );
+
+        let while_loop = AstNode::WhileLoop {
+            condition: Box::new(AstNode::String("yes".to_string())),
+            body: Box::new(AstNode::Block {
+                statements: vec![],
+                discards_value: false,
+            }),
+        };
+        assert_eq!(
+            interpreter.interpret(&while_loop),
+            Err("Condition must be a boolean, found string yes".to_string())
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let mut interpreter = Interpreter::new();
+        let arms = vec![
+            MatchArm {
+                pattern: Pattern::Literal(AstNode::Integer(1)),
+                body: AstNode::String("one".to_string()),
+            },
+            MatchArm {
+                pattern: Pattern::Literal(AstNode::Integer(2)),
+                body: AstNode::String("two".to_string()),
+            },
+        ];
+        let with_wildcard = |scrutinee: i32| {
+            let mut arms = arms.clone();
+            arms.push(MatchArm {
+                pattern: Pattern::Wildcard,
+                body: AstNode::String("other".to_string()),
+            });
+            AstNode::Match {
+                scrutinee: Box::new(AstNode::Integer(scrutinee)),
+                arms,
+            }
+        };
+
+        assert_eq!(
+            interpreter.interpret(&with_wildcard(2)),
+            Ok(Value::String("two".to_string()))
+        );
+        assert_eq!(
+            interpreter.interpret(&with_wildcard(7)),
+            Ok(Value::String("other".to_string()))
+        );
+
+        let non_exhaustive = AstNode::Match {
+            scrutinee: Box::new(AstNode::Integer(7)),
+            arms,
+        };
+        assert!(interpreter.interpret(&non_exhaustive).is_err());
+    }
+
+    #[test]
+    fn test_tuples() {
+        let mut interpreter = Interpreter::new();
+        let divmod = AstNode::FunctionDecl {
+            name: "divmod".into(),
+            params: vec![("a".into(), Type::I32), ("b".into(), Type::I32)],
+            defaults: vec![],
+            return_type: Some(Type::Tuple(vec![Type::I32, Type::I32])),
+            body: Box::new(AstNode::Block {
+                statements: vec![AstNode::Tuple(vec![
+                    AstNode::BinaryOp {
+                        left: Box::new(AstNode::Identifier("a".into())),
+                        operator: Operator::Div,
+                        right: Box::new(AstNode::Identifier("b".into())),
+                    },
+                    AstNode::BinaryOp {
+                        left: Box::new(AstNode::Identifier("a".into())),
+                        operator: Operator::Mod,
+                        right: Box::new(AstNode::Identifier("b".into())),
+                    },
+                ])],
+                discards_value: false,
+            }),
+            attributes: vec![],
+            is_async: false,
+        };
+        interpreter.interpret(&divmod).unwrap();
+
+        let destructure = AstNode::DestructureDecl {
+            names: vec!["q".into(), "r".into()],
+            type_annotation: None,
+            initializer: Box::new(AstNode::FunctionCall {
+                name: "divmod".into(),
+                args: vec![AstNode::Integer(7), AstNode::Integer(2)],
+            }),
+        };
+        assert_eq!(
+            interpreter.interpret(&destructure).unwrap(),
+            Value::Tuple(vec![Value::Integer(3), Value::Integer(1)])
+        );
+        assert_eq!(
+            interpreter.interpret(&AstNode::Identifier("r".into())),
+            Ok(Value::Integer(1))
+        );
+
+        let access = AstNode::TupleAccess {
+            target: Box::new(AstNode::Tuple(vec![
+                AstNode::Integer(4),
+                AstNode::Integer(5),
+            ])),
+            index: 1,
+        };
+        assert_eq!(interpreter.interpret(&access), Ok(Value::Integer(5)));
+    }
+
+    #[test]
+    fn test_display_collections() {
+        let nested = Value::Vector(vec![
+            Value::Integer(1),
+            Value::Vector(vec![Value::String("a".to_string()), Value::Boolean(true)]),
+        ]);
+        assert_eq!(nested.to_string(), r#"[1, ["a", true]]"#);
+
+        let mut map = HashMap::new();
+        map.insert("k".to_string(), Value::Integer(2));
+        map.insert("a".to_string(), Value::Vector(vec![]));
+        assert_eq!(Value::HashMap(map).to_string(), r#"{"a": [], "k": 2}"#);
+    }
+
+    #[test]
+    fn test_calling_non_function_variable() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define("x", Value::Integer(5));
+        let call = AstNode::FunctionCall {
+            name: "x".into(),
+            args: vec![],
+        };
+        assert_eq!(
+            interpreter.interpret(&call),
+            Err("x is not a function".to_string())
+        );
+    }
+
+    #[test]
+    fn test_compound_division_errors() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define("x", Value::Integer(5));
+        interpreter.define("min", Value::Integer(i32::MIN));
+
+        assert_eq!(
+            interpreter.eval("x /= 0;"),
+            Err("Division by zero".to_string())
+        );
+        assert_eq!(
+            interpreter.eval("x %= 0;"),
+            Err("Modulus by zero".to_string())
+        );
+        assert!(interpreter.eval("min /= -1;").is_err());
+        assert!(interpreter.eval("min %= -1;").is_err());
+        assert_eq!(interpreter.eval("x"), Ok(Value::Integer(5)));
+    }
+
+    #[test]
+    fn test_negative_vector_index() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define(
+            "v",
+            Value::Vector(vec![
+                Value::Integer(10),
+                Value::Integer(20),
+                Value::Integer(30),
+            ]),
+        );
+
+        assert_eq!(interpreter.eval("v[-1]"), Ok(Value::Integer(30)));
+        assert_eq!(interpreter.eval("v[-3]"), Ok(Value::Integer(10)));
+        assert_eq!(interpreter.eval("v[0]"), Ok(Value::Integer(10)));
+        assert_eq!(
+            interpreter.eval("v[-4]"),
+            Err("Index out of bounds".to_string())
+        );
+        assert_eq!(
+            interpreter.eval("v[3]"),
+            Err("Index out of bounds".to_string())
+        );
+    }
+
+    #[test]
+    fn test_collection_equality() {
+        let mut interpreter = Interpreter::new();
+        let nested = |last: i32| {
+            Value::Vector(vec![
+                Value::Integer(1),
+                Value::Vector(vec![Value::Integer(2), Value::Integer(last)]),
+            ])
+        };
+        let map = |value: Value| Value::HashMap(HashMap::from([("k".to_string(), value)]));
+        interpreter.define("a", nested(3));
+        interpreter.define("b", nested(3));
+        interpreter.define("c", nested(4));
+        interpreter.define("m1", map(nested(3)));
+        interpreter.define("m2", map(nested(3)));
+        interpreter.define("m3", map(nested(4)));
+
+        assert_eq!(interpreter.eval("a == b"), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.eval("a == c"), Ok(Value::Boolean(false)));
+        assert_eq!(interpreter.eval("a != c"), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.eval("m1 == m2"), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.eval("m1 != m3"), Ok(Value::Boolean(true)));
+        assert!(interpreter.eval("a == m1").is_err());
+    }
+
+    #[test]
+    fn test_in_operator() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define(
+            "v",
+            Value::Vector(vec![Value::Integer(1), Value::Integer(2)]),
+        );
+        interpreter.define(
+            "m",
+            Value::HashMap(HashMap::from([("key".to_string(), Value::Integer(0))])),
+        );
+
+        assert_eq!(interpreter.eval("2 in v"), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.eval("3 in v"), Ok(Value::Boolean(false)));
+        assert_eq!(interpreter.eval(r#""key" in m"#), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.eval(r#""0" in m"#), Ok(Value::Boolean(false)));
+        assert_eq!(
+            interpreter.eval(r#""ell" in "hello" && !("z" in "hello")"#),
+            Ok(Value::Boolean(true))
+        );
+        assert!(interpreter.eval("1 in 2").is_err());
+    }
+
+    #[test]
+    fn test_unreachable_actors_are_dropped_after_handling_mail() {
+        let mut interpreter = Interpreter::new();
+        let code = "func #actor echo(out: dyn, n: i32) { send(out, n * 2); }
+            let out = channel();
+            func spawn_one(n: i32) { send(echo(out), n); }
+            let i = 0;
+            while i < 3 { spawn_one(i); i += 1; }
+            let kept = echo(out);";
+        assert!(interpreter.eval(code).is_ok());
+        // Mail queued without a `recv` is still handled by the end of eval
+        assert_eq!(
+            interpreter.eval("(recv(out), recv(out), recv(out))"),
+            Ok(Value::Tuple(vec![
+                Value::Integer(0),
+                Value::Integer(2),
+                Value::Integer(4)
+            ]))
+        );
+        assert_eq!(interpreter.actors.len(), 1);
+    }
+
+    #[test]
+    fn test_scopes_captured_by_their_own_functions_are_freed() {
+        let probe = Shared::new(Value::Integer(0));
+        let mut interpreter = Interpreter::new();
+        interpreter.define("probe", Value::Shared(probe.clone()));
+        let code = "func outer() { let copy: @i32 = probe; func peek() { copy } peek() }
+            outer();
+            func make() { let kept: @i32 = probe; func() { kept } }
+            let escaped = make();";
+        assert!(interpreter.eval(code).is_ok());
+        // The global binding, ours, and the scope `escaped` still needs
+        assert_eq!(Rc::strong_count(&probe.0), 3);
+        assert_eq!(interpreter.eval("escaped()"), Ok(Value::Integer(0)));
+        drop(interpreter);
+        assert_eq!(Rc::strong_count(&probe.0), 1);
     }
 }
@@ -6,6 +6,8 @@ pub enum Token {
     Integer(i32),
     Float(f64),
     String(String),
+    // A string containing `${...}`
+    Interpolated(Vec<StringPart>),
     Bool(bool),
 
     // Collections
@@ -23,6 +25,7 @@ pub enum Token {
     If,
     Else,
     While,
+    Do,
     For,
     In,
     Return,
@@ -33,6 +36,10 @@ pub enum Token {
     Impl,
     Async,
     Await,
+    Match,
+    Enum,
+    Type,
+    Try,
 
     // Memory Management
     Tilde, // ~ (unique ownership)
@@ -69,7 +76,9 @@ pub enum Token {
     MinusMinus,
     MinusEq,
     Multiply,
+    StarEq,
     Divide,
+    SlashEq,
     Assign,
     Eq,
     NotEq,
@@ -81,6 +90,7 @@ pub enum Token {
     Or,
     Not,
     Modulus,
+    PercentEq,
     LParen,
     RParen,
     LBrace,
@@ -89,21 +99,45 @@ pub enum Token {
     RBracket,
     Comma,
     Dot,
+    Ellipsis,
     Colon,
     DoubleColon,
     Semicolon,
     Arrow,
+    FatArrow,
 
     // Special
     Identifier(String),
     Eof,
     Invalid(char),
+    UnterminatedString(usize), // line where the literal starts
+    InvalidEscape(String),
+    InvalidNumber(String), // why the literal was rejected
 }
 
+// A piece of an interpolated string: literal text, or the tokens of an
+// embedded expression
+#[derive(Debug, PartialEq, Clone)]
+pub enum StringPart {
+    Text(String),
+    Code(Vec<Token>),
+}
+
+// 1-based line and column in the source
+pub type Position = (usize, usize);
+
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     current_char: Option<char>,
+    // Line of the current character and where that line starts, kept up to
+    // date as the lexer advances
+    line: usize,
+    line_start: usize,
+    // Where the last returned token starts
+    token_start: Position,
+    // Whether the last token ends an operand, so a `.` after it is an access
+    after_operand: bool,
 }
 
 impl Lexer {
@@ -115,10 +149,23 @@ impl Lexer {
             input: chars,
             position: 0,
             current_char,
+            line: 1,
+            line_start: 0,
+            token_start: (1, 1),
+            after_operand: false,
         }
     }
 
+    // Line and column (both 1-based) where the last returned token starts
+    pub fn token_position(&self) -> Position {
+        self.token_start
+    }
+
     fn advance(&mut self) {
+        if self.current_char == Some('\n') {
+            self.line += 1;
+            self.line_start = self.position + 1;
+        }
         self.position += 1;
         self.current_char = self.input.get(self.position).copied();
     }
@@ -127,6 +174,13 @@ impl Lexer {
         self.input.get(self.position + 1).copied()
     }
 
+    // Whether the `.` at the current position starts a float like `.5`. After
+    // something that ends an operand, as in `pair.0` or `(a, b).1`, it's an
+    // access instead.
+    fn starts_float(&self) -> bool {
+        !self.after_operand && self.peek().is_some_and(|c| c.is_ascii_digit())
+    }
+
     fn skip_whitespace(&mut self) {
         while let Some(c) = self.current_char {
             if !c.is_whitespace() {
@@ -139,24 +193,52 @@ impl Lexer {
     fn read_number(&mut self) -> Token {
         let mut number = String::new();
         let mut is_float = false;
+        let mut malformed = false;
+        // A number right after a single `.` is a tuple index, as in the `0`
+        // of `t.0.1`, so the `.` after it starts another access
+        let is_index = self.position >= 1
+            && self.input[self.position - 1] == '.'
+            && (self.position < 2 || self.input[self.position - 2] != '.');
 
         while let Some(c) = self.current_char {
             if c.is_ascii_digit() {
                 number.push(c);
                 self.advance();
+            } else if c == '.' && is_index {
+                break;
             } else if c == '.' && !is_float {
+                // `5.` is 5.0
                 is_float = true;
                 number.push(c);
                 self.advance();
+            } else if c == '.'
+                && self
+                    .input
+                    .get(self.position + 1)
+                    .is_some_and(char::is_ascii_digit)
+            {
+                // A second decimal point, as in `1.2.3`
+                malformed = true;
+                number.push(c);
+                self.advance();
             } else {
                 break;
             }
         }
 
-        if is_float {
-            Token::Float(number.parse().unwrap())
+        if malformed {
+            Token::InvalidNumber(format!("Malformed number literal: {}", number))
+        } else if is_float {
+            match number.parse() {
+                Ok(f) => Token::Float(f),
+                Err(_) => Token::InvalidNumber(format!("Invalid float literal: {}", number)),
+            }
         } else {
-            Token::Integer(number.parse().unwrap())
+            // Only digits were read, so the parse can only fail on overflow
+            match number.parse() {
+                Ok(n) => Token::Integer(n),
+                Err(_) => Token::InvalidNumber(format!("Integer literal too large: {}", number)),
+            }
         }
     }
 
@@ -179,6 +261,7 @@ impl Lexer {
             "if" => Token::If,
             "else" => Token::Else,
             "while" => Token::While,
+            "do" => Token::Do,
             "for" => Token::For,
             "in" => Token::In,
             "return" => Token::Return,
@@ -189,6 +272,12 @@ impl Lexer {
             "impl" => Token::Impl,
             "async" => Token::Async,
             "await" => Token::Await,
+            "match" => Token::Match,
+            "enum" => Token::Enum,
+            "type" => Token::Type,
+            "try" => Token::Try,
+            "true" => Token::Bool(true),
+            "false" => Token::Bool(false),
 
             // Types
             "i8" => Token::TypeI8,
@@ -243,28 +332,124 @@ impl Lexer {
         }
     }
 
+    fn line_at(&self, position: usize) -> usize {
+        self.input[..position]
+            .iter()
+            .filter(|&&c| c == '\n')
+            .count()
+            + 1
+    }
+
+    // Reads the escape sequence following a backslash, leaving the lexer on
+    // the character after it
+    fn read_escape(&mut self) -> Result<char, String> {
+        let next = self
+            .current_char
+            .ok_or("Unexpected end of input in escape sequence".to_string())?;
+        self.advance();
+
+        match next {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            '$' => Ok('$'),
+            'x' => {
+                let mut digits = String::new();
+                for _ in 0..2 {
+                    match self.current_char {
+                        Some(c) if c.is_ascii_hexdigit() => {
+                            digits.push(c);
+                            self.advance();
+                        }
+                        _ => return Err("\\x escape expects two hex digits".to_string()),
+                    }
+                }
+                let byte = u8::from_str_radix(&digits, 16).map_err(|e| e.to_string())?;
+                if byte > 0x7F {
+                    return Err(format!("\\x{} is out of range (max \\x7F)", digits));
+                }
+                Ok(byte as char)
+            }
+            'u' => {
+                if self.current_char != Some('{') {
+                    return Err("\\u escape expects '{'".to_string());
+                }
+                self.advance();
+
+                let mut digits = String::new();
+                while let Some(c) = self.current_char {
+                    if c == '}' {
+                        break;
+                    }
+                    if !c.is_ascii_hexdigit() || digits.len() == 6 {
+                        return Err(format!("Invalid character {:?} in \\u escape", c));
+                    }
+                    digits.push(c);
+                    self.advance();
+                }
+                if self.current_char != Some('}') {
+                    return Err("Unterminated \\u escape".to_string());
+                }
+                self.advance();
+
+                if digits.is_empty() {
+                    return Err("Empty \\u{} escape".to_string());
+                }
+                let code = u32::from_str_radix(&digits, 16).map_err(|e| e.to_string())?;
+                char::from_u32(code).ok_or(format!("Invalid unicode codepoint \\u{{{}}}", digits))
+            }
+            other => Ok(other),
+        }
+    }
+
     fn read_string(&mut self) -> Token {
+        let start = self.position;
         self.advance(); // Skip opening quote
         let mut string = String::new();
+        let mut parts = Vec::new();
+        let mut escape_error = None;
 
         while let Some(c) = self.current_char {
             match c {
                 '"' => {
                     self.advance(); // Skip closing quote
-                    return Token::String(string);
+                    if let Some(error) = escape_error {
+                        return error;
+                    }
+                    if parts.is_empty() {
+                        return Token::String(string);
+                    }
+                    parts.push(StringPart::Text(string));
+                    return Token::Interpolated(parts);
+                }
+                '$' if self.peek() == Some('{') => {
+                    self.advance();
+                    self.advance(); // Skip `${`
+                    match self.read_interpolation(start) {
+                        Ok(tokens) => {
+                            parts.push(StringPart::Text(std::mem::take(&mut string)));
+                            parts.push(StringPart::Code(tokens));
+                        }
+                        Err(Token::UnterminatedString(line)) => {
+                            return Token::UnterminatedString(line)
+                        }
+                        Err(error) => {
+                            escape_error.get_or_insert(error);
+                        }
+                    }
                 }
                 '\\' => {
                     self.advance();
-                    if let Some(next) = self.current_char {
-                        string.push(match next {
-                            'n' => '\n',
-                            't' => '\t',
-                            'r' => '\r',
-                            '"' => '"',
-                            '\\' => '\\',
-                            _ => next,
-                        });
-                        self.advance();
+                    match self.read_escape() {
+                        Ok(escaped) => string.push(escaped),
+                        // Keep scanning to the closing quote so the rest of
+                        // the literal isn't lexed as code
+                        Err(message) => {
+                            escape_error.get_or_insert(Token::InvalidEscape(message));
+                        }
                     }
                 }
                 _ => {
@@ -273,177 +458,330 @@ impl Lexer {
                 }
             }
         }
-        Token::Invalid('"') // Unterminated string
+        Token::UnterminatedString(self.line_at(start))
+    }
+
+    // Lexes the expression in `${...}`, up to the brace that closes it. A
+    // malformed expression gives the error token its lexer produced.
+    fn read_interpolation(&mut self, string_start: usize) -> Result<Vec<Token>, Token> {
+        let mut source = String::new();
+        let mut depth = 0;
+        let mut in_string = false;
+
+        while let Some(c) = self.current_char {
+            self.advance();
+            match c {
+                '\\' if in_string => {
+                    source.push(c);
+                    if let Some(escaped) = self.current_char {
+                        source.push(escaped);
+                        self.advance();
+                    }
+                    continue;
+                }
+                '"' => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string && depth == 0 => {
+                    let mut lexer = Lexer::new(source);
+                    let mut tokens = Vec::new();
+                    loop {
+                        match lexer.next_token() {
+                            Token::Eof => return Ok(tokens),
+                            Token::UnterminatedString(_) => {
+                                return Err(Token::UnterminatedString(self.line_at(string_start)))
+                            }
+                            error @ (Token::Invalid(_)
+                            | Token::InvalidEscape(_)
+                            | Token::InvalidNumber(_)) => return Err(error),
+                            token => tokens.push(token),
+                        }
+                    }
+                }
+                '}' if !in_string => depth -= 1,
+                _ => {}
+            }
+            source.push(c);
+        }
+        Err(Token::UnterminatedString(self.line_at(string_start)))
+    }
+
+    // `"""..."""` keeps everything up to the closing quotes as written,
+    // newlines and backslashes included
+    fn read_triple_quoted_string(&mut self) -> Token {
+        let start = self.position;
+        for _ in 0..3 {
+            self.advance(); // Skip opening quotes
+        }
+        let mut string = String::new();
+
+        while let Some(c) = self.current_char {
+            if c == '"'
+                && self.peek() == Some('"')
+                && self.input.get(self.position + 2) == Some(&'"')
+            {
+                for _ in 0..3 {
+                    self.advance(); // Skip closing quotes
+                }
+                return Token::String(string);
+            }
+            string.push(c);
+            self.advance();
+        }
+        Token::UnterminatedString(self.line_at(start))
+    }
+
+    // Number of `#`s in a raw string opener `r#"` starting at the current
+    // `r`, or None if it isn't one
+    fn raw_string_hashes(&self) -> Option<usize> {
+        let hashes = self.input[self.position + 1..]
+            .iter()
+            .take_while(|&&c| c == '#')
+            .count();
+        (self.input.get(self.position + 1 + hashes) == Some(&'"')).then_some(hashes)
+    }
+
+    // `r"..."` ends at the next quote and processes no escapes. Any number of
+    // `#`s may surround the quotes, so `r#"say "hi""#` can contain quotes.
+    fn read_raw_string(&mut self, hashes: usize) -> Token {
+        let start = self.position;
+        for _ in 0..hashes + 2 {
+            self.advance(); // Skip `r`, the `#`s and the opening quote
+        }
+        let mut string = String::new();
+
+        while let Some(c) = self.current_char {
+            let closes =
+                c == '"' && (1..=hashes).all(|i| self.input.get(self.position + i) == Some(&'#'));
+            if closes {
+                for _ in 0..hashes + 1 {
+                    self.advance(); // Skip the closing quote and `#`s
+                }
+                return Token::String(string);
+            }
+            string.push(c);
+            self.advance();
+        }
+        Token::UnterminatedString(self.line_at(start))
     }
 
     pub fn next_token(&mut self) -> Token {
+        let token = self.read_token();
+        self.after_operand = matches!(
+            token,
+            Token::Identifier(_)
+                | Token::Integer(_)
+                | Token::Float(_)
+                | Token::String(_)
+                | Token::Interpolated(_)
+                | Token::Bool(_)
+                | Token::RParen
+                | Token::RBracket
+        );
+        token
+    }
+
+    fn read_token(&mut self) -> Token {
         self.skip_whitespace();
+        let start = self.position.min(self.input.len());
+        self.token_start = (self.line, start - self.line_start + 1);
 
         match self.current_char {
             None => Token::Eof,
-            Some(c) => {
-                if self.position > self.input.len() * 2 {
-                    return Token::Eof;
+            // Every branch below consumes at least one character, so repeated
+            // calls always reach the end of input
+            Some(c) => match c {
+                '0'..='9' => self.read_number(),
+                '.' if self.starts_float() => self.read_number(),
+                'r' if self.raw_string_hashes().is_some() => {
+                    let hashes = self.raw_string_hashes().unwrap_or_default();
+                    self.read_raw_string(hashes)
                 }
-
-                match c {
-                    '0'..='9' => self.read_number(),
-                    'a'..='z' | 'A'..='Z' | '_' => self.read_identifier(),
-                    '#' => self.read_attribute(),
-                    '"' => self.read_string(),
-                    '~' => {
+                'a'..='z' | 'A'..='Z' | '_' => self.read_identifier(),
+                '#' => self.read_attribute(),
+                '"' if self.peek() == Some('"')
+                    && self.input.get(self.position + 2) == Some(&'"') =>
+                {
+                    self.read_triple_quoted_string()
+                }
+                '"' => self.read_string(),
+                '~' => {
+                    self.advance();
+                    Token::Tilde
+                }
+                '@' => {
+                    self.advance();
+                    Token::At
+                }
+                '+' => {
+                    self.advance();
+                    if self.current_char == Some('=') {
                         self.advance();
-                        Token::Tilde
-                    }
-                    '@' => {
+                        Token::PlusEq
+                    } else if self.current_char == Some('+') {
                         self.advance();
-                        Token::At
+                        Token::PlusPlus
+                    } else {
+                        Token::Plus
                     }
-                    '+' => {
+                }
+                '-' => {
+                    self.advance();
+                    if self.current_char == Some('>') {
                         self.advance();
-                        if self.current_char == Some('=') {
-                            self.advance();
-                            Token::PlusEq
-                        } else if self.current_char == Some('+') {
-                            self.advance();
-                            Token::PlusPlus
-                        } else {
-                            Token::Plus
-                        }
-                    }
-                    '-' => {
+                        Token::Arrow
+                    } else if self.current_char == Some('=') {
                         self.advance();
-                        if self.current_char == Some('>') {
-                            self.advance();
-                            Token::Arrow
-                        } else if self.current_char == Some('=') {
-                            self.advance();
-                            Token::MinusEq
-                        } else if self.current_char == Some('-') {
-                            self.advance();
-                            Token::MinusMinus
-                        } else {
-                            Token::Minus
-                        }
+                        Token::MinusEq
+                    } else if self.current_char == Some('-') {
+                        self.advance();
+                        Token::MinusMinus
+                    } else {
+                        Token::Minus
                     }
-                    '*' => {
+                }
+                '*' => {
+                    self.advance();
+                    if self.current_char == Some('=') {
                         self.advance();
+                        Token::StarEq
+                    } else {
                         Token::Multiply
                     }
-                    '/' => {
+                }
+                '/' => {
+                    self.advance();
+                    if self.current_char == Some('=') {
                         self.advance();
+                        Token::SlashEq
+                    } else {
                         Token::Divide
                     }
-                    '=' => {
-                        self.advance();
-                        if self.current_char == Some('=') {
-                            self.advance();
-                            Token::Eq
-                        } else {
-                            Token::Assign
-                        }
-                    }
-                    '!' => {
-                        self.advance();
-                        if self.current_char == Some('=') {
-                            self.advance();
-                            Token::NotEq
-                        } else {
-                            Token::Not
-                        }
-                    }
-                    '<' => {
-                        self.advance();
-                        if self.current_char == Some('=') {
-                            self.advance();
-                            Token::LtEq
-                        } else {
-                            Token::Lt
-                        }
-                    }
-                    '>' => {
-                        self.advance();
-                        if self.current_char == Some('=') {
-                            self.advance();
-                            Token::GtEq
-                        } else {
-                            Token::Gt
-                        }
-                    }
-                    '&' => {
-                        self.advance();
-                        if self.current_char == Some('&') {
-                            self.advance();
-                            Token::And
-                        } else {
-                            Token::Invalid('&')
-                        }
-                    }
-                    '|' => {
+                }
+                '=' => {
+                    self.advance();
+                    if self.current_char == Some('=') {
                         self.advance();
-                        if self.current_char == Some('|') {
-                            self.advance();
-                            Token::Or
-                        } else {
-                            Token::Invalid('|')
-                        }
-                    }
-                    ':' => {
+                        Token::Eq
+                    } else if self.current_char == Some('>') {
                         self.advance();
-                        if self.current_char == Some(':') {
-                            self.advance();
-                            Token::DoubleColon
-                        } else {
-                            Token::Colon
-                        }
+                        Token::FatArrow
+                    } else {
+                        Token::Assign
                     }
-                    '(' => {
+                }
+                '!' => {
+                    self.advance();
+                    if self.current_char == Some('=') {
                         self.advance();
-                        Token::LParen
+                        Token::NotEq
+                    } else {
+                        Token::Not
                     }
-                    ')' => {
+                }
+                '<' => {
+                    self.advance();
+                    if self.current_char == Some('=') {
                         self.advance();
-                        Token::RParen
+                        Token::LtEq
+                    } else {
+                        Token::Lt
                     }
-                    '{' => {
+                }
+                '>' => {
+                    self.advance();
+                    if self.current_char == Some('=') {
                         self.advance();
-                        Token::LBrace
+                        Token::GtEq
+                    } else {
+                        Token::Gt
                     }
-                    '}' => {
+                }
+                '&' => {
+                    self.advance();
+                    if self.current_char == Some('&') {
                         self.advance();
-                        Token::RBrace
+                        Token::And
+                    } else {
+                        Token::Invalid('&')
                     }
-                    '[' => {
+                }
+                '|' => {
+                    self.advance();
+                    if self.current_char == Some('|') {
                         self.advance();
-                        Token::LBracket
+                        Token::Or
+                    } else {
+                        Token::Invalid('|')
                     }
-                    ']' => {
+                }
+                ':' => {
+                    self.advance();
+                    if self.current_char == Some(':') {
                         self.advance();
-                        Token::RBracket
+                        Token::DoubleColon
+                    } else {
+                        Token::Colon
                     }
-                    ',' => {
+                }
+                '(' => {
+                    self.advance();
+                    Token::LParen
+                }
+                ')' => {
+                    self.advance();
+                    Token::RParen
+                }
+                '{' => {
+                    self.advance();
+                    Token::LBrace
+                }
+                '}' => {
+                    self.advance();
+                    Token::RBrace
+                }
+                '[' => {
+                    self.advance();
+                    Token::LBracket
+                }
+                ']' => {
+                    self.advance();
+                    Token::RBracket
+                }
+                ',' => {
+                    self.advance();
+                    Token::Comma
+                }
+                '.' => {
+                    self.advance();
+                    if self.current_char == Some('.')
+                        && self.input.get(self.position + 1) == Some(&'.')
+                    {
                         self.advance();
-                        Token::Comma
-                    }
-                    '.' => {
                         self.advance();
+                        Token::Ellipsis
+                    } else {
                         Token::Dot
                     }
-                    ';' => {
-                        self.advance();
-                        Token::Semicolon
-                    }
-                    '%' => {
+                }
+                ';' => {
+                    self.advance();
+                    Token::Semicolon
+                }
+                '%' => {
+                    self.advance();
+                    if self.current_char == Some('=') {
                         self.advance();
+                        Token::PercentEq
+                    } else {
                         Token::Modulus
                     }
-                    _ => {
-                        let invalid = c;
-                        self.advance();
-                        Token::Invalid(invalid)
-                    }
                 }
-            }
+                _ => {
+                    let invalid = c;
+                    self.advance();
+                    Token::Invalid(invalid)
+                }
+            },
         }
     }
 }
@@ -480,4 +818,247 @@ mod tests {
         assert_eq!(lexer.next_token(), Token::Identifier("String".to_string()));
         assert_eq!(lexer.next_token(), Token::RBrace);
     }
+
+    #[test]
+    fn test_unterminated_string() {
+        let mut lexer = Lexer::new("let a = 1;\nlet s = \"oops;\n".to_string());
+
+        for _ in 0..8 {
+            lexer.next_token();
+        }
+        assert_eq!(lexer.next_token(), Token::UnterminatedString(2));
+    }
+
+    #[test]
+    fn test_triple_quoted_strings() {
+        let source = "let t = \"\"\"{\n  \"path\": \"C:\\tmp\"\n}\"\"\"; \"\"";
+        let mut lexer = Lexer::new(source.to_string());
+
+        for _ in 0..3 {
+            lexer.next_token();
+        }
+        assert_eq!(
+            lexer.next_token(),
+            Token::String("{\n  \"path\": \"C:\\tmp\"\n}".to_string())
+        );
+        assert_eq!(lexer.next_token(), Token::Semicolon);
+        assert_eq!(lexer.next_token(), Token::String(String::new()));
+
+        let mut lexer = Lexer::new("\n\"\"\"never\nclosed\"\"".to_string());
+        assert_eq!(lexer.next_token(), Token::UnterminatedString(2));
+    }
+
+    #[test]
+    fn test_interpolated_strings() {
+        let mut lexer = Lexer::new(r#""n=${n + 1}!" "\${n}" "${f("}")}""#.to_string());
+
+        assert_eq!(
+            lexer.next_token(),
+            Token::Interpolated(vec![
+                StringPart::Text("n=".to_string()),
+                StringPart::Code(vec![
+                    Token::Identifier("n".to_string()),
+                    Token::Plus,
+                    Token::Integer(1),
+                ]),
+                StringPart::Text("!".to_string()),
+            ])
+        );
+        assert_eq!(lexer.next_token(), Token::String("${n}".to_string()));
+        assert_eq!(
+            lexer.next_token(),
+            Token::Interpolated(vec![
+                StringPart::Text(String::new()),
+                StringPart::Code(vec![
+                    Token::Identifier("f".to_string()),
+                    Token::LParen,
+                    Token::String("}".to_string()),
+                    Token::RParen,
+                ]),
+                StringPart::Text(String::new()),
+            ])
+        );
+
+        let mut lexer = Lexer::new(r#""open ${n"#.to_string());
+        assert_eq!(lexer.next_token(), Token::UnterminatedString(1));
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        let mut lexer = Lexer::new(r###"r"C:\path\to" r#"say "hi"\n"# r"" raw"###.to_string());
+
+        assert_eq!(lexer.next_token(), Token::String(r"C:\path\to".to_string()));
+        assert_eq!(
+            lexer.next_token(),
+            Token::String(r#"say "hi"\n"#.to_string())
+        );
+        assert_eq!(lexer.next_token(), Token::String(String::new()));
+        assert_eq!(lexer.next_token(), Token::Identifier("raw".to_string()));
+
+        let mut lexer = Lexer::new(r##"r#"unclosed""##.to_string());
+        assert_eq!(lexer.next_token(), Token::UnterminatedString(1));
+    }
+
+    #[test]
+    fn test_extended_escapes() {
+        let mut lexer = Lexer::new(r#""\u{1F600}\x41\0""#.to_string());
+        assert_eq!(
+            lexer.next_token(),
+            Token::String("\u{1F600}A\0".to_string())
+        );
+
+        for invalid in [r#""\u{}""#, r#""\u{110000}""#, r#""\x8""#] {
+            let mut lexer = Lexer::new(invalid.to_string());
+            assert!(
+                matches!(lexer.next_token(), Token::InvalidEscape(_)),
+                "{} should be rejected",
+                invalid
+            );
+            assert_eq!(lexer.next_token(), Token::Eof);
+        }
+    }
+
+    #[test]
+    fn test_integer_literal_out_of_range() {
+        let mut lexer = Lexer::new("2147483647 9999999999 1".to_string());
+        assert_eq!(lexer.next_token(), Token::Integer(i32::MAX));
+        assert_eq!(
+            lexer.next_token(),
+            Token::InvalidNumber("Integer literal too large: 9999999999".to_string())
+        );
+        assert_eq!(lexer.next_token(), Token::Integer(1));
+    }
+
+    #[test]
+    fn test_float_literal_edge_cases() {
+        let mut lexer = Lexer::new(".5 5. . 1.2.3 4".to_string());
+        assert_eq!(lexer.next_token(), Token::Float(0.5));
+        assert_eq!(lexer.next_token(), Token::Float(5.0));
+        assert_eq!(lexer.next_token(), Token::Dot);
+        assert_eq!(
+            lexer.next_token(),
+            Token::InvalidNumber("Malformed number literal: 1.2.3".to_string())
+        );
+        assert_eq!(lexer.next_token(), Token::Integer(4));
+    }
+
+    #[test]
+    fn test_leading_dot_floats() {
+        let mut lexer =
+            Lexer::new("x = .5 * (.25) obj.field pair.0 (1, 2) .1 return .5 in .5".to_string());
+        let tokens: Vec<Token> = std::iter::from_fn(|| match lexer.next_token() {
+            Token::Eof => None,
+            token => Some(token),
+        })
+        .collect();
+        let ident = |name: &str| Token::Identifier(name.to_string());
+        assert_eq!(
+            tokens,
+            vec![
+                ident("x"),
+                Token::Assign,
+                Token::Float(0.5),
+                Token::Multiply,
+                Token::LParen,
+                Token::Float(0.25),
+                Token::RParen,
+                ident("obj"),
+                Token::Dot,
+                ident("field"),
+                ident("pair"),
+                Token::Dot,
+                Token::Integer(0),
+                Token::LParen,
+                Token::Integer(1),
+                Token::Comma,
+                Token::Integer(2),
+                Token::RParen,
+                Token::Dot,
+                Token::Integer(1),
+                // Keywords don't end an operand
+                Token::Return,
+                Token::Float(0.5),
+                Token::In,
+                Token::Float(0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment_tokens() {
+        let mut lexer = Lexer::new("*= /= %= * / %".to_string());
+
+        assert_eq!(lexer.next_token(), Token::StarEq);
+        assert_eq!(lexer.next_token(), Token::SlashEq);
+        assert_eq!(lexer.next_token(), Token::PercentEq);
+        assert_eq!(lexer.next_token(), Token::Multiply);
+        assert_eq!(lexer.next_token(), Token::Divide);
+        assert_eq!(lexer.next_token(), Token::Modulus);
+    }
+
+    #[test]
+    fn test_ellipsis_needs_three_dots() {
+        let mut lexer = Lexer::new("...i32 t.0 ..".to_string());
+
+        assert_eq!(lexer.next_token(), Token::Ellipsis);
+        assert_eq!(lexer.next_token(), Token::TypeI32);
+        assert_eq!(lexer.next_token(), Token::Identifier("t".to_string()));
+        assert_eq!(lexer.next_token(), Token::Dot);
+        assert_eq!(lexer.next_token(), Token::Integer(0));
+        assert_eq!(lexer.next_token(), Token::Dot);
+        assert_eq!(lexer.next_token(), Token::Dot);
+    }
+
+    #[test]
+    fn test_nested_tuple_indices() {
+        let mut lexer = Lexer::new("t.0.1 (x).2.0".to_string());
+        let tokens: Vec<Token> = std::iter::from_fn(|| match lexer.next_token() {
+            Token::Eof => None,
+            token => Some(token),
+        })
+        .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Identifier("t".to_string()),
+                Token::Dot,
+                Token::Integer(0),
+                Token::Dot,
+                Token::Integer(1),
+                Token::LParen,
+                Token::Identifier("x".to_string()),
+                Token::RParen,
+                Token::Dot,
+                Token::Integer(2),
+                Token::Dot,
+                Token::Integer(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_input_lexes_to_the_end() {
+        let mut lexer = Lexer::new("x += 1; ".repeat(10_000));
+
+        for _ in 0..10_000 {
+            assert_eq!(lexer.next_token(), Token::Identifier("x".to_string()));
+            assert_eq!(lexer.next_token(), Token::PlusEq);
+            assert_eq!(lexer.next_token(), Token::Integer(1));
+            assert_eq!(lexer.next_token(), Token::Semicolon);
+        }
+        assert_eq!(lexer.next_token(), Token::Eof);
+    }
+
+    #[test]
+    fn test_token_positions_span_lines() {
+        let mut lexer = Lexer::new("let s = \"\"\"a\nb\"\"\";\n\n  s".to_string());
+        let mut positions = Vec::new();
+        while lexer.next_token() != Token::Eof {
+            positions.push(lexer.token_position());
+        }
+        assert_eq!(
+            positions,
+            vec![(1, 1), (1, 5), (1, 7), (1, 9), (2, 5), (4, 3)]
+        );
+    }
 }
@@ -0,0 +1,108 @@
+//! Animikiikode as a library. `Interpreter::eval` runs source text directly;
+//! the modules below expose each stage for hosts that need finer control.
+//!
+//! ```
+//! use animikiikode::{Interpreter, Value};
+//!
+//! let mut interpreter = Interpreter::new();
+//! assert_eq!(interpreter.eval("let x = 4; x * 10 + 2"), Ok(Value::Integer(42)));
+//! ```
+
+pub mod diagnostics;
+pub mod interner;
+pub mod interpreter;
+pub mod lexer;
+pub mod optimizer;
+pub mod parser;
+pub mod resolver;
+pub mod stdlib;
+pub mod vm;
+
+pub use interpreter::{Interpreter, Value};
+pub use stdlib::Io;
+
+use std::collections::HashSet;
+
+use diagnostics::Diagnostic;
+use lexer::{Lexer, Position, Token};
+use parser::{AstNode, Parser};
+
+// Lexes `source` without parsing it. Invalid input is reported with the line
+// and column where it starts.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    Ok(tokenize_with_positions(source)
+        .map_err(|(message, _)| message)?
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect())
+}
+
+// Like `tokenize`, pairing each token with where it starts. Errors also
+// carry where the invalid input starts.
+pub fn tokenize_with_positions(source: &str) -> Result<Vec<(Token, Position)>, Diagnostic> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut tokens = Vec::new();
+
+    loop {
+        let token = lexer.next_token();
+        let (line, column) = lexer.token_position();
+        let message = match token {
+            Token::Eof => break,
+            Token::Invalid(c) => format!(
+                "Invalid character: {} at line {}, column {}",
+                c, line, column
+            ),
+            Token::InvalidEscape(message) => format!(
+                "Invalid escape sequence: {} at line {}, column {}",
+                message, line, column
+            ),
+            Token::InvalidNumber(message) => {
+                format!("{} at line {}, column {}", message, line, column)
+            }
+            Token::UnterminatedString(line) => {
+                format!("Unterminated string literal starting at line {}", line)
+            }
+            _ => {
+                tokens.push((token, (line, column)));
+                continue;
+            }
+        };
+        return Err((message, Some((line, column))));
+    }
+
+    Ok(tokens)
+}
+
+// Lexes and parses `source` without running it, reporting every syntax error
+pub fn parse(source: &str) -> Result<Vec<AstNode>, String> {
+    Parser::new(tokenize(source)?)
+        .parse_recovering()
+        .map_err(|errors| errors.join("\n"))
+}
+
+// Like `parse`, marking the first statement on each of `breakpoints` with an
+// `AstNode::Breakpoint`
+pub fn parse_with_breakpoints(
+    source: &str,
+    breakpoints: &HashSet<usize>,
+) -> Result<Vec<AstNode>, String> {
+    parser_for(source, breakpoints)
+        .map_err(|(message, _)| message)?
+        .parse_recovering()
+        .map_err(|errors| errors.join("\n"))
+}
+
+// A parser for `source` that marks `breakpoints`, if there are any
+pub(crate) fn parser_for(source: &str, breakpoints: &HashSet<usize>) -> Result<Parser, Diagnostic> {
+    let tokens = tokenize_with_positions(source)?;
+    if breakpoints.is_empty() {
+        return Ok(Parser::new(
+            tokens.into_iter().map(|(token, _)| token).collect(),
+        ));
+    }
+    let (tokens, lines) = tokens
+        .into_iter()
+        .map(|(token, (line, _))| (token, line))
+        .unzip();
+    Ok(Parser::new(tokens).with_breakpoints(lines, breakpoints.clone()))
+}
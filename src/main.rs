@@ -1,61 +1,156 @@
-use interpreter::Value;
-use lexer::Token;
+use animikiikode::diagnostics::{self, Diagnostic};
+use animikiikode::parser::{Parser, Type};
+use animikiikode::{Interpreter, Value};
 use log::{error, info};
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::process::Command;
+use std::time::{Duration, Instant};
 
-mod interpreter;
-mod lexer;
-mod parser;
-mod stdlib;
+// Runs a script file, or the program piped to stdin when `path` is "-", in
+// an interpreter the caller has configured
+fn execute_file(path: &str, interpreter: &mut Interpreter) -> Result<(), String> {
+    info!("Executing file: {}", path);
+    let content = read_source(path)?;
+    execute_script(&content, interpreter).map_err(|e| report(&content, e))
+}
 
-use crate::interpreter::Interpreter;
-use crate::lexer::Lexer;
-use crate::parser::Parser;
+// Reads a script file, or stdin when `path` is "-"
+fn read_source(path: &str) -> Result<String, String> {
+    if path == "-" {
+        read_program(io::stdin())
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))
+    }
+}
 
-fn execute_file(path: &str) -> Result<(), String> {
-    info!("Executing file: {}", path);
-    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
-    let mut interpreter = Interpreter::new();
-    execute_code(&content, &mut interpreter)
+fn read_program(mut reader: impl Read) -> Result<String, String> {
+    let mut content = String::new();
+    reader
+        .read_to_string(&mut content)
+        .map_err(|e| format!("Failed to read program: {}", e))?;
+    Ok(content)
 }
 
-fn execute_code(source: &str, interpreter: &mut Interpreter) -> Result<(), String> {
-    let mut lexer = Lexer::new(source.to_string());
-    let mut tokens = Vec::new();
+// Drops a leading `#!` line so scripts can be made executable. The newline is
+// kept so line numbers in errors still match the file.
+fn strip_shebang(source: &str) -> &str {
+    if source.starts_with("#!") {
+        &source[source.find('\n').unwrap_or(source.len())..]
+    } else {
+        source
+    }
+}
 
-    loop {
-        let token = lexer.next_token();
-        match token {
-            Token::Eof => break,
-            Token::Invalid(c) => return Err(format!("Invalid character: {}", c)),
-            _ => tokens.push(token),
+// Renders an error against the source it came from, for printing to stderr
+fn report(source: &str, (message, position): Diagnostic) -> String {
+    diagnostics::render(source, &message, position, diagnostics::use_color())
+}
+
+// Runs every `.aki` file in `dir` as a script, in name order and each with a
+// fresh interpreter. Returns how many passed and each failure's path and error.
+fn run_test_files(dir: &str) -> Result<(usize, Vec<(String, String)>), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir, e))?;
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "aki"))
+        .collect();
+    paths.sort();
+
+    let mut passed = 0;
+    let mut failures = Vec::new();
+    for path in paths {
+        let name = path.display().to_string();
+        let result = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read file: {}", e))
+            .and_then(|content| {
+                execute_script(&content, &mut Interpreter::new()).map_err(|e| report(&content, e))
+            });
+        match result {
+            Ok(()) => passed += 1,
+            Err(e) => failures.push((name, e)),
         }
     }
+    Ok((passed, failures))
+}
 
-    let mut parser = Parser::new(tokens);
-    let ast = parser.parse()?;
+// `aki test <dir>`: prints each failure and a summary, failing if any script did
+fn run_tests(dir: &str) -> Result<(), String> {
+    let (passed, failures) = run_test_files(dir)?;
+    for (name, error) in &failures {
+        eprintln!("FAIL {}\n{}", name, error.trim_end());
+    }
+    println!("{} passed, {} failed", passed, failures.len());
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} test script(s) failed", failures.len()))
+    }
+}
 
-    for node in ast {
-        match interpreter.interpret(node) {
-            Ok(value) => {
-                if !matches!(value, Value::Unit) {
-                    println!("====> {:?}", value);
-                }
-            }
-            Err(e) => {
-                error!("Execution error: {}", e);
-                return Err(e);
-            }
-        }
+// `--profile` output: one row per function with its calls and total time
+fn format_profile(report: &[(String, u64, Duration)]) -> String {
+    let mut output = format!("{:<24} {:>8} {:>12}\n", "function", "calls", "time");
+    for (name, calls, time) in report {
+        output.push_str(&format!("{:<24} {:>8} {:>12.3?}\n", name, calls, time));
     }
+    output
+}
 
+// Prints the parsed program as an indented tree without running it
+fn dump_ast(path: &str) -> Result<(), String> {
+    let content = read_source(path)?;
+    let tokens = animikiikode::tokenize_with_positions(strip_shebang(&content))
+        .map_err(|e| report(&content, e))?;
+    let ast = Parser::new(tokens.into_iter().map(|(token, _)| token).collect())
+        .parse_recovering()
+        .map_err(|errors| report(&content, (errors.join("\n"), None)))?;
+    print!("{}", animikiikode::parser::pretty_print(&ast));
     Ok(())
 }
 
+// One token per line, prefixed with the line and column it starts at
+fn format_tokens(source: &str) -> Result<String, Diagnostic> {
+    Ok(animikiikode::tokenize_with_positions(source)?
+        .into_iter()
+        .map(|(token, (line, column))| format!("{}:{} {:?}\n", line, column, token))
+        .collect())
+}
+
+fn dump_tokens(path: &str) -> Result<(), String> {
+    let content = read_source(path)?;
+    print!(
+        "{}",
+        format_tokens(strip_shebang(&content)).map_err(|e| report(&content, e))?
+    );
+    Ok(())
+}
+
+// Runs a whole program, then its `main` function if it has one. The REPL uses
+// execute_code directly so defining `main` there doesn't run it.
+fn execute_script(source: &str, interpreter: &mut Interpreter) -> Result<(), Diagnostic> {
+    execute_code(strip_shebang(source), interpreter)?;
+    interpreter.run_main().map(|_| ()).map_err(|e| (e, None))
+}
+
+fn execute_code(source: &str, interpreter: &mut Interpreter) -> Result<(), Diagnostic> {
+    let ast = interpreter.prepare(source)?;
+    interpreter.hoist_functions(&ast);
+
+    for node in &ast {
+        let value = interpreter.interpret(node).map_err(|e| (e, None))?;
+        if !matches!(value, Value::Unit) {
+            println!("====> {:?}", value);
+        }
+    }
+
+    interpreter.deliver_pending().map_err(|e| (e, None))
+}
+
 fn run_repl() -> io::Result<()> {
     println!("Animikiikode REPL v{}", env!("CARGO_PKG_VERSION"));
+    println!("Type :env to list definitions, :reset to forget them, exit to quit");
+    println!("Type :type <expr> to show the type of an expression, :time <expr> to time it");
 
     let mut interpreter = Interpreter::new();
 
@@ -80,10 +175,37 @@ fn run_repl() -> io::Result<()> {
                     break;
                 }
 
+                if trimmed == ":env" || trimmed == ":vars" {
+                    print!("{}", format_bindings(&interpreter));
+                    continue;
+                }
+
+                if let Some(expr) = trimmed.strip_prefix(":type ") {
+                    match interpreter.eval(expr) {
+                        Ok(value) => println!("{}", describe_type(&value)),
+                        Err(e) => eprint!("{}", report(expr, (e, None))),
+                    }
+                    continue;
+                }
+
+                if let Some(expr) = trimmed.strip_prefix(":time ") {
+                    match time_eval(expr, &mut interpreter) {
+                        Ok((value, elapsed)) => println!("====> {:?} ({:?})", value, elapsed),
+                        Err(e) => eprint!("{}", report(expr, (e, None))),
+                    }
+                    continue;
+                }
+
+                if trimmed == ":reset" {
+                    interpreter.reset();
+                    println!("Environment cleared");
+                    continue;
+                }
+
                 info!("Processing input: {}", trimmed);
                 match execute_code(trimmed, &mut interpreter) {
                     Ok(_) => (),
-                    Err(e) => eprintln!("Error: {}", e),
+                    Err(e) => eprint!("{}", report(trimmed, e)),
                 }
             }
             Err(error) => {
@@ -95,6 +217,50 @@ fn run_repl() -> io::Result<()> {
     Ok(())
 }
 
+// A value's type as the REPL shows it: functions by their signature, other
+// values by their inferred type or, failing that, their kind
+fn describe_type(value: &Value) -> String {
+    match value {
+        Value::Function {
+            params, is_async, ..
+        } => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty))
+                .collect();
+            let prefix = if *is_async { "async func" } else { "func" };
+            format!("{}({})", prefix, params.join(", "))
+        }
+        _ => value
+            .inferred_type()
+            .map_or_else(|| value.type_name().to_string(), |ty| ty.to_string()),
+    }
+}
+
+// Evaluates `source`, returning its value and how long that took, for `:time`
+fn time_eval(source: &str, interpreter: &mut Interpreter) -> Result<(Value, Duration), String> {
+    let start = Instant::now();
+    let value = interpreter.eval(source)?;
+    Ok((value, start.elapsed()))
+}
+
+// One line per binding in the interpreter's scope, for `:env`
+fn format_bindings(interpreter: &Interpreter) -> String {
+    interpreter
+        .bindings()
+        .into_iter()
+        .map(|(name, value, ty)| match value {
+            Value::Function { .. } => format!("{}: {}\n", name, describe_type(&value)),
+            _ => {
+                let ty = ty
+                    .as_ref()
+                    .map_or_else(|| describe_type(&value), Type::to_string);
+                format!("{}: {} = {}\n", name, ty, value)
+            }
+        })
+        .collect()
+}
+
 pub fn clear_screen_native() -> io::Result<()> {
     if cfg!(target_os = "windows") {
         Command::new("cmd").args(["/C", "cls"]).status()?;
@@ -116,6 +282,11 @@ pub fn clear_screen() {
     }
 }
 
+// Stack size for the interpreter thread. The tree-walking interpreter recurses
+// on the native stack, so this needs enough room for DEFAULT_MAX_CALL_DEPTH
+// nested calls even in unoptimized builds.
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() {
     env_logger::init();
 
@@ -126,31 +297,124 @@ fn main() {
 
     clear_screen();
 
-    let args: Vec<String> = std::env::args().collect();
-    match args.len() {
-        1 => {
+    let handle = std::thread::Builder::new()
+        .name("interpreter".to_string())
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(run)
+        .expect("Failed to spawn interpreter thread");
+
+    if handle.join().is_err() {
+        std::process::exit(1);
+    }
+}
+
+// Flags given before the script path. Any combination of the run flags is
+// allowed; --ast and --tokens only parse the script, so they ignore them.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    ast: bool,
+    tokens: bool,
+    sandbox: bool,
+    profile: bool,
+    max_steps: Option<usize>,
+    path: Option<String>,
+}
+
+// Parses everything after the program name. Errors are printed with the
+// usage text.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ast" => options.ast = true,
+            "--tokens" => options.tokens = true,
+            "--sandbox" => options.sandbox = true,
+            "--profile" => options.profile = true,
+            "--max-steps" => {
+                let steps = args.next().ok_or("--max-steps needs a step count")?;
+                let steps = steps
+                    .parse()
+                    .map_err(|_| format!("Invalid step count: {}", steps))?;
+                options.max_steps = Some(steps);
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown flag: {}", flag)),
+            path if options.path.is_none() => options.path = Some(path.to_string()),
+            extra => return Err(format!("Unexpected argument: {}", extra)),
+        }
+    }
+    if options.ast && options.tokens {
+        return Err("--ast and --tokens can't be used together".to_string());
+    }
+    Ok(options)
+}
+
+// Runs the script at `path` in an interpreter set up by the run flags
+fn run_script(options: &Options, path: &str) -> Result<(), String> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_sandbox(options.sandbox);
+    interpreter.set_max_steps(options.max_steps);
+    interpreter.set_profiling(options.profile);
+    let result = execute_file(path, &mut interpreter);
+    if options.profile {
+        eprint!("{}", format_profile(&interpreter.profile_report()));
+    }
+    result
+}
+
+fn print_usage() {
+    println!(
+        "Usage: aki [--ast | --tokens] [--sandbox] [--profile] [--max-steps <n>] [script.aki | -]"
+    );
+    println!("       aki test <dir>");
+}
+
+fn run() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [] => {
             if let Err(e) = run_repl() {
                 error!("REPL error: {}", e);
                 std::process::exit(1);
             }
+            Ok(())
         }
-        2 => {
-            let file_path = &args[1];
-            if let Err(e) = execute_file(file_path) {
-                error!("Execution error: {}", e);
+        [command, dir] if command == "test" => run_tests(dir),
+        _ => match parse_args(&args) {
+            Ok(Options {
+                path: Some(path),
+                ast: true,
+                ..
+            }) => dump_ast(&path),
+            Ok(Options {
+                path: Some(path),
+                tokens: true,
+                ..
+            }) => dump_tokens(&path),
+            Ok(options) => match &options.path {
+                Some(path) => run_script(&options, path),
+                None => {
+                    print_usage();
+                    std::process::exit(1);
+                }
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                print_usage();
                 std::process::exit(1);
             }
-        }
-        _ => {
-            println!("Usage: aki [script.aki]");
-            std::process::exit(1);
-        }
+        },
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e.trim_end());
+        std::process::exit(1);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use animikiikode::{parser, Io};
 
     #[test]
     fn test_basic_execution() {
@@ -172,10 +436,439 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_integer_and_float_division() {
+        let mut interpreter = Interpreter::new();
+        let code = "let a: i32 = 3 / 2; let b: f64 = fdiv(3, 2);";
+        assert!(execute_code(code, &mut interpreter).is_ok());
+        assert_eq!(
+            interpreter.interpret(&parser::AstNode::Identifier("a".into())),
+            Ok(Value::Integer(1))
+        );
+        assert_eq!(
+            interpreter.interpret(&parser::AstNode::Identifier("b".into())),
+            Ok(Value::Float(1.5))
+        );
+        assert!(execute_code("fdiv(1, 0);", &mut interpreter).is_err());
+    }
+
+    #[test]
+    fn test_mutating_builtins_persist() {
+        let mut interpreter = Interpreter::new();
+        let code = r#"
+            let v = Vec::new();
+            push(v, 1);
+            push(v, 2);
+            push(v, 3);
+            pop(v);
+            set(v, 0, 10);
+        "#;
+        assert!(execute_code(code, &mut interpreter).is_ok());
+        assert_eq!(
+            interpreter.interpret(&parser::AstNode::Identifier("v".into())),
+            Ok(Value::Vector(vec![Value::Integer(10), Value::Integer(2)]))
+        );
+    }
+
+    #[test]
+    fn test_default_call_depth_fits_the_interpreter_stack() {
+        use animikiikode::interpreter::DEFAULT_MAX_CALL_DEPTH;
+
+        // Run as the CLI does, on a thread with the interpreter's stack size
+        let results = std::thread::Builder::new()
+            .stack_size(INTERPRETER_STACK_SIZE)
+            .spawn(|| {
+                let mut interpreter = Interpreter::new();
+                interpreter
+                    .eval("func down(n: i32) -> i32 { if n == 0 { 0 } else { 1 + down(n - 1) } }")
+                    .unwrap();
+                // Values stay on the interpreter's thread, so send back their text
+                let mut depth = |n: usize| {
+                    (interpreter.eval(&format!("down({})", n))).map(|value| value.to_string())
+                };
+                // down(n) is n + 1 nested calls
+                (
+                    depth(DEFAULT_MAX_CALL_DEPTH - 1),
+                    depth(DEFAULT_MAX_CALL_DEPTH),
+                )
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(results.0, Ok((DEFAULT_MAX_CALL_DEPTH - 1).to_string()));
+        assert_eq!(
+            results.1,
+            Err(format!(
+                "Maximum recursion depth exceeded ({})",
+                DEFAULT_MAX_CALL_DEPTH
+            ))
+        );
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_call_depth(2);
+        let code = r#"
+            func a() -> i32 { 1 }
+            func b() -> i32 { a() }
+            func c() -> i32 { b() }
+        "#;
+        assert!(execute_code(code, &mut interpreter).is_ok());
+        assert!(execute_code("b();", &mut interpreter).is_ok());
+
+        let result = execute_code("c();", &mut interpreter);
+        assert!(result
+            .unwrap_err()
+            .0
+            .contains("Maximum recursion depth exceeded"));
+
+        // The depth counter unwinds after an error
+        assert!(execute_code("b();", &mut interpreter).is_ok());
+    }
+
+    #[test]
+    fn test_undefined_function_reported_before_execution() {
+        let mut interpreter = Interpreter::new();
+        let code = r#"
+            let x: i32 = 1;
+            if x > 5 {
+                prnitln(x);
+            }
+        "#;
+        let result = execute_code(code, &mut interpreter);
+        assert_eq!(
+            result,
+            Err(("Undefined function: prnitln".to_string(), None))
+        );
+        // Nothing ran, so `x` was never defined
+        assert!(interpreter.defined_names().is_empty());
+    }
+
+    #[test]
+    fn test_multiplicative_compound_assignment() {
+        let mut interpreter = Interpreter::new();
+        let code = r#"
+            let a: i32 = 6;
+            a *= 7;
+            let b: i32 = 42;
+            b /= 5;
+            let c: i32 = 42;
+            c %= 5;
+            let d: f64 = 1.5;
+            d *= 3.0;
+            d /= 0.5;
+        "#;
+        assert!(execute_code(code, &mut interpreter).is_ok());
+
+        let lookup = |interpreter: &mut Interpreter, name: &str| {
+            interpreter.interpret(&parser::AstNode::Identifier(name.into()))
+        };
+        assert_eq!(lookup(&mut interpreter, "a"), Ok(Value::Integer(42)));
+        assert_eq!(lookup(&mut interpreter, "b"), Ok(Value::Integer(8)));
+        assert_eq!(lookup(&mut interpreter, "c"), Ok(Value::Integer(2)));
+        assert_eq!(lookup(&mut interpreter, "d"), Ok(Value::Float(9.0)));
+    }
+
+    #[test]
+    fn test_unterminated_string_message() {
+        let mut interpreter = Interpreter::new();
+        let result = execute_code("let a = 1;\nprintln(\"hello);", &mut interpreter);
+        assert_eq!(
+            result,
+            Err((
+                "Unterminated string literal starting at line 2".to_string(),
+                Some((2, 9))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_all_syntax_errors_reported() {
+        let mut interpreter = Interpreter::new();
+        let result = execute_code("let a: i32 = ;\nlet b = 2;\nlet c = );", &mut interpreter);
+        assert_eq!(result.unwrap_err().0.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_closure_captures_by_reference() {
+        let mut interpreter = Interpreter::new();
+        let code = r#"
+            func make_counter() {
+                let n = 0;
+                func() { n += 1; n }
+            }
+            let counter = make_counter();
+            let other = make_counter();
+            counter();
+            counter();
+            let a = counter();
+            let b = other();
+        "#;
+        assert!(execute_code(code, &mut interpreter).is_ok());
+        assert_eq!(
+            interpreter.interpret(&parser::AstNode::Identifier("a".into())),
+            Ok(Value::Integer(3))
+        );
+        assert_eq!(
+            interpreter.interpret(&parser::AstNode::Identifier("b".into())),
+            Ok(Value::Integer(1))
+        );
+    }
+
+    #[test]
+    fn test_recursive_function_in_nested_scope() {
+        let mut interpreter = Interpreter::new();
+        let code = r#"
+            func outer() {
+                if true {
+                    func fib(n: i32) -> i32 {
+                        if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+                    }
+                    fib(10)
+                } else {
+                    0
+                }
+            }
+            let result = outer();
+        "#;
+        assert!(execute_code(code, &mut interpreter).is_ok());
+        assert_eq!(
+            interpreter.interpret(&parser::AstNode::Identifier("result".into())),
+            Ok(Value::Integer(55))
+        );
+    }
+
+    #[test]
+    fn test_script_io_uses_injected_reader_and_writer() {
+        #[derive(Clone, Default)]
+        struct Captured(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Captured::default();
+        let input = io::Cursor::new("Ada\n3\n");
+        let mut interpreter = Interpreter::with_io(Io::new(input, output.clone()));
+        let code = r#"
+            let name = prompt("Name: ");
+            let times = to_int(input());
+            println("Hello,", name);
+            print(times * 2);
+        "#;
+
+        assert!(execute_code(code, &mut interpreter).is_ok());
+        assert_eq!(
+            String::from_utf8(output.0.borrow().clone()).unwrap(),
+            "Name: Hello, Ada\n6"
+        );
+    }
+
+    #[test]
+    fn test_functions_callable_before_declaration() {
+        let mut interpreter = Interpreter::new();
+        let code = r#"
+            let result = 0;
+            func main() { result = helper(4); }
+            func helper(n: i32) -> i32 { if is_even(n) { n * 10 } else { n } }
+            func is_even(n: i32) -> bool { if n == 0 { true } else { is_odd(n - 1) } }
+            func is_odd(n: i32) -> bool { if n == 0 { false } else { is_even(n - 1) } }
+        "#;
+        assert!(execute_script(code, &mut interpreter).is_ok());
+        assert_eq!(
+            interpreter.interpret(&parser::AstNode::Identifier("result".into())),
+            Ok(Value::Integer(40))
+        );
+    }
+
+    #[test]
+    fn test_main_runs_after_the_whole_script() {
+        let mut interpreter = Interpreter::new();
+        let code = r#"
+            let calls = 0;
+            func main() { calls += 1; calls = later(calls); }
+            let before_main = calls;
+            func later(n: i32) -> i32 { n * 10 }
+        "#;
+
+        assert!(execute_code(code, &mut interpreter).is_ok());
+        assert_eq!(interpreter.eval("calls"), Ok(Value::Integer(0)));
+
+        let mut interpreter = Interpreter::new();
+        assert!(execute_script(code, &mut interpreter).is_ok());
+        assert_eq!(interpreter.eval("before_main"), Ok(Value::Integer(0)));
+        assert_eq!(interpreter.eval("calls"), Ok(Value::Integer(10)));
+    }
+
+    #[test]
+    fn test_format_tokens() {
+        assert_eq!(
+            format_tokens("let x = 1;\nx").unwrap(),
+            "1:1 Let\n\
+             1:5 Identifier(\"x\")\n\
+             1:7 Assign\n\
+             1:9 Integer(1)\n\
+             1:10 Semicolon\n\
+             2:1 Identifier(\"x\")\n"
+        );
+    }
+
+    #[test]
+    fn test_read_program_from_reader() {
+        let program = read_program(io::Cursor::new("let x = 2;\nlet y = x * 3;")).unwrap();
+        let mut interpreter = Interpreter::new();
+        assert!(execute_script(&program, &mut interpreter).is_ok());
+        assert_eq!(interpreter.eval("y"), Ok(Value::Integer(6)));
+
+        let empty = read_program(io::empty()).unwrap();
+        assert_eq!(empty, "");
+        assert!(execute_script(&empty, &mut Interpreter::new()).is_ok());
+    }
+
+    #[test]
+    fn test_shebang_line_is_skipped() {
+        let mut interpreter = Interpreter::new();
+        let code = "#!/usr/bin/env aki\nlet x = 1;\nfunc main() { x = 2; }\n";
+        assert!(execute_script(code, &mut interpreter).is_ok());
+        assert_eq!(interpreter.eval("x"), Ok(Value::Integer(2)));
+
+        assert_eq!(strip_shebang("#!aki\nlet"), "\nlet");
+        assert_eq!(strip_shebang("let x;\n#!aki"), "let x;\n#!aki");
+        assert!(execute_script("let x = 1;\n#!aki", &mut Interpreter::new()).is_err());
+    }
+
     #[test]
     fn test_invalid_syntax() {
         let mut interpreter = Interpreter::new();
         let result = execute_code("let x: i32 = ;", &mut interpreter);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_env_listing_shows_bindings() {
+        let mut interpreter = Interpreter::new();
+        let code = r#"
+            let count: u8 = 3;
+            let name = "Ada";
+            func add(x: i32, y: dyn) -> i32 { x + y }
+        "#;
+        assert!(execute_code(code, &mut interpreter).is_ok());
+        assert_eq!(
+            format_bindings(&interpreter),
+            "add: func(x: i32, y: dyn)\ncount: u8 = 3\nname: string = Ada\n"
+        );
+    }
+
+    #[test]
+    fn test_describe_type() {
+        let mut interpreter = Interpreter::new();
+        let mut type_of = |source: &str| describe_type(&interpreter.eval(source).unwrap());
+        assert_eq!(type_of("1 + 2"), "i32");
+        assert_eq!(type_of("\"hi\""), "string");
+        assert_eq!(type_of("(1, 2.5, true)"), "(i32, f64, bool)");
+        assert_eq!(type_of("Vec::new()"), "Vec<dyn>");
+        assert_eq!(
+            type_of("func(n: i32, rest: ...dyn) { n }"),
+            "func(n: i32, rest: ...dyn)"
+        );
+        assert_eq!(type_of("enum Coin { Heads } Coin::Heads"), "Coin");
+        assert_eq!(type_of("channel()"), "channel");
+        assert_eq!(type_of("()"), "()");
+    }
+
+    #[test]
+    fn test_time_eval_returns_value_and_duration() {
+        let mut interpreter = Interpreter::new();
+        let (value, elapsed) =
+            time_eval("let i = 0; while i < 1000 { i += 1 } i", &mut interpreter).unwrap();
+        assert_eq!(value, Value::Integer(1000));
+        assert!(elapsed > Duration::ZERO);
+        assert!(time_eval("1 / 0", &mut interpreter).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_combines_flags() {
+        let args = |line: &str| -> Vec<String> { line.split(' ').map(String::from).collect() };
+        assert_eq!(
+            parse_args(&args("--sandbox --max-steps 1000 --profile script.aki")),
+            Ok(Options {
+                sandbox: true,
+                profile: true,
+                max_steps: Some(1000),
+                path: Some("script.aki".to_string()),
+                ..Options::default()
+            })
+        );
+        assert_eq!(
+            parse_args(&args("- --sandbox")),
+            Ok(Options {
+                sandbox: true,
+                path: Some("-".to_string()),
+                ..Options::default()
+            })
+        );
+        assert_eq!(
+            parse_args(&args("--max-steps many a.aki")),
+            Err("Invalid step count: many".to_string())
+        );
+        assert!(parse_args(&args("--max-steps")).is_err());
+        assert!(parse_args(&args("--fast a.aki")).is_err());
+        assert!(parse_args(&args("a.aki b.aki")).is_err());
+        assert!(parse_args(&args("--ast --tokens a.aki")).is_err());
+    }
+
+    #[test]
+    fn test_format_profile() {
+        let report = vec![("helper".to_string(), 3, Duration::from_micros(1500))];
+        assert_eq!(
+            format_profile(&report),
+            format!(
+                "{:<24} {:>8} {:>12}\n{:<24} {:>8} {:>12}\n",
+                "function", "calls", "time", "helper", 3, "1.500ms"
+            )
+        );
+    }
+
+    #[test]
+    fn test_runner_counts_passing_and_failing_scripts() {
+        let dir = std::env::temp_dir().join(format!("aki_test_runner_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("a_pass.aki"),
+            "func main() { assert(6 / 2 == 3, \"halves\") }",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b_fail.aki"),
+            "func main() { assert(7 / 2 == 4, \"integer division\") }",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("c_error.aki"),
+            "let zero = 0;\nfunc main() { 1 / zero }",
+        )
+        .unwrap();
+        fs::write(dir.join("notes.txt"), "not a script").unwrap();
+
+        let result = run_test_files(&dir.to_string_lossy());
+        let summary = run_tests(&dir.to_string_lossy());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (passed, failures) = result.unwrap();
+        assert_eq!(passed, 1);
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].0.ends_with("b_fail.aki"));
+        assert!(failures[0].1.contains("Assertion failed: integer division"));
+        assert!(failures[1].0.ends_with("c_error.aki"));
+        assert!(failures[1].1.contains("Division by zero"));
+        assert!(summary.is_err());
+    }
 }
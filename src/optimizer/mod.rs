@@ -0,0 +1,308 @@
+use crate::interpreter::{Interpreter, Value};
+use crate::parser::{AstNode, MatchArm};
+
+// Constant folding: replaces operator subtrees built only from literals with
+// the literal they evaluate to. Literals have no side effects, so evaluating
+// them early is safe. A subtree that fails to evaluate (e.g. `1 / 0`) is left
+// as is so the error is raised when the program reaches it, not before.
+pub fn fold_constants(nodes: Vec<AstNode>) -> Vec<AstNode> {
+    let mut folder = Folder {
+        evaluator: Interpreter::new(),
+    };
+    nodes.into_iter().map(|node| folder.fold(node)).collect()
+}
+
+struct Folder {
+    evaluator: Interpreter,
+}
+
+fn is_literal(node: &AstNode) -> bool {
+    matches!(
+        node,
+        AstNode::Integer(_) | AstNode::Float(_) | AstNode::String(_) | AstNode::Boolean(_)
+    )
+}
+
+fn to_literal(value: Value) -> Option<AstNode> {
+    match value {
+        Value::Integer(n) => Some(AstNode::Integer(n)),
+        Value::Float(f) => Some(AstNode::Float(f)),
+        Value::String(s) => Some(AstNode::String(s)),
+        Value::Boolean(b) => Some(AstNode::Boolean(b)),
+        _ => None,
+    }
+}
+
+impl Folder {
+    // Folds in place, reusing the box's allocation
+    fn fold_box(&mut self, mut node: Box<AstNode>) -> Box<AstNode> {
+        *node = self.fold(std::mem::replace(&mut *node, AstNode::ChannelCreate));
+        node
+    }
+
+    fn fold_all(&mut self, nodes: Vec<AstNode>) -> Vec<AstNode> {
+        nodes.into_iter().map(|node| self.fold(node)).collect()
+    }
+
+    // Evaluates a node whose operands are all literals, keeping the original
+    // node if evaluation fails or doesn't produce a literal
+    fn evaluate(&mut self, node: AstNode) -> AstNode {
+        self.evaluator
+            .interpret(&node)
+            .ok()
+            .and_then(to_literal)
+            .unwrap_or(node)
+    }
+
+    fn fold(&mut self, node: AstNode) -> AstNode {
+        match node {
+            AstNode::BinaryOp {
+                left,
+                operator,
+                right,
+            } => {
+                let node = AstNode::BinaryOp {
+                    left: self.fold_box(left),
+                    operator,
+                    right: self.fold_box(right),
+                };
+                match &node {
+                    AstNode::BinaryOp { left, right, .. }
+                        if is_literal(left) && is_literal(right) =>
+                    {
+                        self.evaluate(node)
+                    }
+                    _ => node,
+                }
+            }
+            AstNode::UnaryOp { operator, operand } => {
+                let operand = self.fold_box(operand);
+                let foldable = is_literal(&operand);
+                let node = AstNode::UnaryOp { operator, operand };
+                if foldable {
+                    self.evaluate(node)
+                } else {
+                    node
+                }
+            }
+            AstNode::VariableDecl {
+                name,
+                type_annotation,
+                initializer,
+                ownership,
+            } => AstNode::VariableDecl {
+                name,
+                type_annotation,
+                initializer: initializer.map(|init| self.fold_box(init)),
+                ownership,
+            },
+            AstNode::DestructureDecl {
+                names,
+                type_annotation,
+                initializer,
+            } => AstNode::DestructureDecl {
+                names,
+                type_annotation,
+                initializer: self.fold_box(initializer),
+            },
+            AstNode::FunctionDecl {
+                name,
+                params,
+                defaults,
+                return_type,
+                body,
+                attributes,
+                is_async,
+            } => AstNode::FunctionDecl {
+                name,
+                params,
+                defaults: self.fold_all(defaults),
+                return_type,
+                body: self.fold_box(body),
+                attributes,
+                is_async,
+            },
+            AstNode::Lambda {
+                params,
+                defaults,
+                return_type,
+                body,
+            } => AstNode::Lambda {
+                params,
+                defaults: self.fold_all(defaults),
+                return_type,
+                body: self.fold_box(body),
+            },
+            AstNode::FunctionCall { name, args } => AstNode::FunctionCall {
+                name,
+                args: self.fold_all(args),
+            },
+            AstNode::EnumVariant {
+                enum_name,
+                variant,
+                payload,
+            } => AstNode::EnumVariant {
+                enum_name,
+                variant,
+                payload: payload.map(|payload| self.fold_box(payload)),
+            },
+            AstNode::NamedArgument { name, value } => AstNode::NamedArgument {
+                name,
+                value: self.fold_box(value),
+            },
+            AstNode::IndexAccess { target, index } => AstNode::IndexAccess {
+                target: self.fold_box(target),
+                index: self.fold_box(index),
+            },
+            AstNode::Tuple(elements) => AstNode::Tuple(self.fold_all(elements)),
+            AstNode::Interpolation(parts) => AstNode::Interpolation(self.fold_all(parts)),
+            AstNode::TupleAccess { target, index } => AstNode::TupleAccess {
+                target: self.fold_box(target),
+                index,
+            },
+            AstNode::Block {
+                statements,
+                discards_value,
+            } => AstNode::Block {
+                statements: self.fold_all(statements),
+                discards_value,
+            },
+            AstNode::IfExpr {
+                condition,
+                then_branch,
+                else_branch,
+            } => AstNode::IfExpr {
+                condition: self.fold_box(condition),
+                then_branch: self.fold_box(then_branch),
+                else_branch: else_branch.map(|branch| self.fold_box(branch)),
+            },
+            AstNode::WhileLoop { condition, body } => AstNode::WhileLoop {
+                condition: self.fold_box(condition),
+                body: self.fold_box(body),
+            },
+            AstNode::DoWhile { body, condition } => AstNode::DoWhile {
+                body: self.fold_box(body),
+                condition: self.fold_box(condition),
+            },
+            AstNode::Match { scrutinee, arms } => AstNode::Match {
+                scrutinee: self.fold_box(scrutinee),
+                arms: arms
+                    .into_iter()
+                    .map(|arm| MatchArm {
+                        pattern: arm.pattern,
+                        body: self.fold(arm.body),
+                    })
+                    .collect(),
+            },
+            AstNode::Try {
+                body,
+                binding,
+                handler,
+            } => AstNode::Try {
+                body: self.fold_box(body),
+                binding,
+                handler: self.fold_box(handler),
+            },
+            AstNode::CompoundAssign {
+                operator,
+                target,
+                value,
+            } => AstNode::CompoundAssign {
+                operator,
+                target,
+                value: self.fold_box(value),
+            },
+            AstNode::Send { channel, value } => AstNode::Send {
+                channel: self.fold_box(channel),
+                value: self.fold_box(value),
+            },
+            AstNode::Receive { channel } => AstNode::Receive {
+                channel: self.fold_box(channel),
+            },
+            AstNode::Await { expression } => AstNode::Await {
+                expression: self.fold_box(expression),
+            },
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use crate::parser::Operator;
+
+    fn initializers(source: &str) -> Vec<AstNode> {
+        fold_constants(parse(source).unwrap())
+            .into_iter()
+            .map(|node| match node {
+                AstNode::VariableDecl {
+                    initializer: Some(init),
+                    ..
+                } => *init,
+                other => other,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_folds_literal_expressions() {
+        assert_eq!(
+            initializers("let a = 2 + 3 * 4; let b = -(1 + 1); let c = 1 < 2 && true;"),
+            vec![
+                AstNode::Integer(14),
+                AstNode::Integer(-2),
+                AstNode::Boolean(true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leaves_overflowing_arithmetic_unfolded() {
+        let overflow = |operator, left, right| AstNode::BinaryOp {
+            left: Box::new(AstNode::Integer(left)),
+            operator,
+            right: Box::new(AstNode::Integer(right)),
+        };
+        assert_eq!(
+            initializers(
+                "let a = 2147483647 + 1; let b = 0 - 2147483647 - 2; let c = 65536 * 65536;"
+            ),
+            vec![
+                overflow(Operator::Add, 2147483647, 1),
+                overflow(Operator::Sub, -2147483647, 2),
+                overflow(Operator::Mul, 65536, 65536),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leaves_identifiers_and_errors_unfolded() {
+        assert_eq!(
+            initializers("let a = x + 2 * 3; let b = 1 / 0;"),
+            vec![
+                AstNode::BinaryOp {
+                    left: Box::new(AstNode::Identifier("x".into())),
+                    operator: Operator::Add,
+                    right: Box::new(AstNode::Integer(6)),
+                },
+                AstNode::BinaryOp {
+                    left: Box::new(AstNode::Integer(1)),
+                    operator: Operator::Div,
+                    right: Box::new(AstNode::Integer(0)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_folded_program_errors_at_the_same_point() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.eval("let a = 2 + 3 * 4; let b = 1 / 0;"),
+            Err("Division by zero".to_string())
+        );
+        assert_eq!(interpreter.eval("a"), Ok(Value::Integer(14)));
+    }
+}
@@ -1,5 +1,11 @@
 #![allow(dead_code)]
-use crate::{lexer::Token, stdlib::StdLib};
+use crate::interner::{Symbol, SymbolMap};
+use crate::lexer::{StringPart, Token};
+use std::collections::HashSet;
+use std::fmt;
+
+mod pretty;
+pub use pretty::pretty_print;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Type {
@@ -21,8 +27,57 @@ pub enum Type {
     Shared(Box<Type>),             // @T
     Vec(Box<Type>),                // Vec<T>
     HashMap(Box<Type>, Box<Type>), // HashMap<K,V>
+    Tuple(Vec<Type>),              // (T, U)
+    Rest(Box<Type>),               // ...T, the last parameter only
+    Named(Symbol),                 // a declared enum; aliases are expanded
+}
+
+// Types print as they are written in source
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I32 => write!(f, "i32"),
+            Type::I64 => write!(f, "i64"),
+            Type::U8 => write!(f, "u8"),
+            Type::U16 => write!(f, "u16"),
+            Type::U32 => write!(f, "u32"),
+            Type::U64 => write!(f, "u64"),
+            Type::F32 => write!(f, "f32"),
+            Type::F64 => write!(f, "f64"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Dynamic => write!(f, "dyn"),
+            Type::Unique(inner) => write!(f, "~{}", inner),
+            Type::Shared(inner) => write!(f, "@{}", inner),
+            Type::Vec(inner) => write!(f, "Vec<{}>", inner),
+            Type::Rest(inner) => write!(f, "...{}", inner),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::HashMap(key, value) => write!(f, "HashMap<{}, {}>", key, value),
+            Type::Tuple(types) => {
+                write!(f, "(")?;
+                for (i, ty) in types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", ty)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
 }
 
+// A function parameter: name and declared type
+pub type Param = (Symbol, Type);
+
+// An enum variant: name and the type of its payload, if it carries one
+pub type Variant = (Symbol, Option<Type>);
+
+// Parameters, defaults of the trailing ones, and return type
+type Signature = (Vec<Param>, Vec<AstNode>, Option<Type>);
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Attribute {
     Weak,
@@ -38,29 +93,62 @@ pub enum AstNode {
     Float(f64),
     String(String),
     Boolean(bool),
+    // An interpolated string: its text and the expressions between them
+    Interpolation(Vec<AstNode>),
 
     // Variables
-    Identifier(String),
+    Identifier(Symbol),
     VariableDecl {
-        name: String,
+        name: Symbol,
         type_annotation: Option<Type>,
         initializer: Option<Box<AstNode>>,
         ownership: Option<Ownership>,
     },
+    DestructureDecl {
+        names: Vec<Symbol>,
+        type_annotation: Option<Type>,
+        initializer: Box<AstNode>,
+    },
 
     // Functions
     FunctionDecl {
-        name: String,
-        params: Vec<(String, Type)>,
+        name: Symbol,
+        params: Vec<Param>,
+        // Default values of the trailing parameters before any rest
+        // parameter, in order
+        defaults: Vec<AstNode>,
         return_type: Option<Type>,
         body: Box<AstNode>,
         attributes: Vec<Attribute>,
         is_async: bool,
     },
     FunctionCall {
-        name: String,
+        name: Symbol,
         args: Vec<AstNode>,
     },
+    // Enums
+    EnumDecl {
+        name: Symbol,
+        variants: Vec<Variant>,
+    },
+    // `Enum::Variant` or `Enum::Variant(payload)`
+    EnumVariant {
+        enum_name: Symbol,
+        variant: Symbol,
+        payload: Option<Box<AstNode>>,
+    },
+
+    // `name: value` in a call's arguments, after any positional ones
+    NamedArgument {
+        name: Symbol,
+        value: Box<AstNode>,
+    },
+    Lambda {
+        params: Vec<Param>,
+        defaults: Vec<AstNode>,
+        return_type: Option<Type>,
+        body: Box<AstNode>,
+    },
 
     // Vector and HashMap access
     IndexAccess {
@@ -68,12 +156,29 @@ pub enum AstNode {
         index: Box<AstNode>,
     },
 
+    // Tuples
+    Tuple(Vec<AstNode>),
+    TupleAccess {
+        target: Box<AstNode>,
+        index: usize,
+    },
+
     // Types and Ownership
     TypeAnnotation(Type),
+    // `type Name = type;`, already expanded by the parser wherever it's used
+    TypeAlias {
+        name: Symbol,
+        ty: Type,
+    },
     Ownership(Ownership),
 
     // Control Flow
-    Block(Vec<AstNode>),
+    // `discards_value` is set when the last statement ends in `;`, which
+    // makes the block evaluate to unit
+    Block {
+        statements: Vec<AstNode>,
+        discards_value: bool,
+    },
     IfExpr {
         condition: Box<AstNode>,
         then_branch: Box<AstNode>,
@@ -83,6 +188,25 @@ pub enum AstNode {
         condition: Box<AstNode>,
         body: Box<AstNode>,
     },
+    // `do { body } while condition`: the body runs before the first check
+    DoWhile {
+        body: Box<AstNode>,
+        condition: Box<AstNode>,
+    },
+    Match {
+        scrutinee: Box<AstNode>,
+        arms: Vec<MatchArm>,
+    },
+    // `try { body } else (binding) { handler }`: runs the handler with the
+    // error message bound if the body fails
+    Try {
+        body: Box<AstNode>,
+        binding: Symbol,
+        handler: Box<AstNode>,
+    },
+    // Marks the first statement of a block on, or continued onto, a source
+    // line with a breakpoint; only emitted by parsers given breakpoints
+    Breakpoint(usize),
 
     // Operations
     BinaryOp {
@@ -114,6 +238,24 @@ pub enum AstNode {
     },
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: AstNode,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Literal(AstNode), // 1, "text", true
+    Wildcard,         // _
+    // `Enum::Variant`, or `Enum::Variant(name)` to bind the payload
+    Variant {
+        enum_name: Symbol,
+        variant: Symbol,
+        binding: Option<Symbol>,
+    },
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Ownership {
     Unique, // ~
@@ -131,7 +273,9 @@ pub enum Operator {
     SelfSub,
     Dec,
     Mul,
+    SelfMul,
     Div,
+    SelfDiv,
     Eq,
     NotEq,
     Lt,
@@ -141,24 +285,98 @@ pub enum Operator {
     And,
     Or,
     Mod,
+    SelfMod,
+    In, // membership in a vector, hashmap keys, or string
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum UnaryOperator {
     Not,
     Neg,
+    // `~` in an expression; in a type it marks unique ownership instead
+    BitNot,
     Inc,
     Dec,
 }
 
+// Keywords the lexer reserves that no construct uses yet. They can't be used
+// as names, so they get their own error instead of a generic token one.
+fn unsupported_keyword(token: &Token) -> Option<&'static str> {
+    match token {
+        Token::For => Some("for"),
+        Token::Return => Some("return"),
+        Token::Mod => Some("mod"),
+        Token::Pub => Some("pub"),
+        Token::Use => Some("use"),
+        Token::Struct => Some("struct"),
+        Token::Impl => Some("impl"),
+        _ => None,
+    }
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    // Type aliases declared so far, as written
+    aliases: SymbolMap<Type>,
+    // The source line of each token, and the lines to mark with breakpoints
+    token_lines: Vec<usize>,
+    breakpoints: HashSet<usize>,
+    // The last line marked in the current block, so a line is only marked
+    // once per block, and every line marked in any block
+    last_breakpoint: Option<usize>,
+    marked: HashSet<usize>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            aliases: SymbolMap::default(),
+            token_lines: Vec::new(),
+            breakpoints: HashSet::new(),
+            last_breakpoint: None,
+            marked: HashSet::new(),
+        }
+    }
+
+    // Starts with the aliases declared by earlier programs, so a REPL or
+    // host running one snippet at a time keeps them
+    pub fn with_aliases(mut self, aliases: SymbolMap<Type>) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    // Marks the first statement on each of `breakpoints` with a
+    // `Breakpoint` node. `token_lines` gives the line of each token.
+    pub fn with_breakpoints(
+        mut self,
+        token_lines: Vec<usize>,
+        breakpoints: HashSet<usize>,
+    ) -> Self {
+        self.token_lines = token_lines;
+        self.breakpoints = breakpoints;
+        self
+    }
+
+    // A breakpoint marker for the statement just parsed from the tokens at
+    // `start..current`: its first line if that's a breakpoint and no earlier
+    // statement in the block was marked for it, otherwise the first
+    // breakpoint on a later line of the statement that nothing inside it
+    // was marked for
+    fn breakpoint_marker(&mut self, start: usize) -> Option<AstNode> {
+        let lines = self.token_lines.get(start..self.current)?;
+        let (&first, &last) = (lines.first()?, lines.last()?);
+        let line = if self.breakpoints.contains(&first) && self.last_breakpoint != Some(first) {
+            first
+        } else {
+            (first + 1..=last)
+                .find(|line| self.breakpoints.contains(line) && !self.marked.contains(line))?
+        };
+        self.last_breakpoint = Some(line);
+        self.marked.insert(line);
+        Some(AstNode::Breakpoint(line))
     }
 
     fn peek(&self) -> Option<&Token> {
@@ -183,24 +401,92 @@ impl Parser {
     pub fn parse(&mut self) -> Result<Vec<AstNode>, String> {
         let mut statements = Vec::new();
         while self.peek().is_some() {
-            statements.push(self.parse_statement()?);
+            let start = self.current;
+            let statement = self.parse_statement()?;
+            statements.extend(self.breakpoint_marker(start));
+            statements.push(statement);
         }
         Ok(statements)
     }
 
+    // Parses the whole input, recovering after each failed statement so that
+    // every syntax error is reported rather than only the first
+    pub fn parse_recovering(&mut self) -> Result<Vec<AstNode>, Vec<String>> {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+
+        while self.peek().is_some() {
+            let start = self.current;
+            match self.parse_statement() {
+                Ok(statement) => {
+                    statements.extend(self.breakpoint_marker(start));
+                    statements.push(statement);
+                }
+                Err(e) => {
+                    errors.push(e);
+                    if self.current == start {
+                        self.advance();
+                    }
+                    self.synchronize();
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Skips to the next likely statement boundary after a parse error
+    fn synchronize(&mut self) {
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Semicolon | Token::RBrace => {
+                    self.advance();
+                    return;
+                }
+                Token::Let
+                | Token::Func
+                | Token::If
+                | Token::While
+                | Token::Do
+                | Token::Match
+                | Token::Try
+                | Token::Enum
+                | Token::Type => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
     fn parse_statement(&mut self) -> Result<AstNode, String> {
+        let statement = self.parse_statement_body()?;
+        // Any statement may end in `;`, which in a block discards its value
+        if self.peek() == Some(&Token::Semicolon) {
+            self.advance();
+        }
+        Ok(statement)
+    }
+
+    fn parse_statement_body(&mut self) -> Result<AstNode, String> {
         match self.peek() {
             Some(Token::Let) => self.parse_variable_declaration(),
+            Some(Token::Func) if self.tokens.get(self.current + 1) == Some(&Token::LParen) => {
+                self.parse_expression()
+            }
             Some(Token::Func) => self.parse_function_declaration(),
+            Some(Token::Async) if self.tokens.get(self.current + 1) == Some(&Token::Func) => {
+                self.parse_function_declaration()
+            }
             Some(Token::If) => self.parse_if_statement(),
             Some(Token::While) => self.parse_while_statement(),
-            Some(Token::Identifier(_)) => {
-                let expr = self.parse_expression()?;
-                if self.peek() == Some(&Token::Semicolon) {
-                    self.advance();
-                }
-                Ok(expr)
-            }
+            Some(Token::Do) => self.parse_do_while_statement(),
+            Some(Token::Enum) => self.parse_enum_declaration(),
+            Some(Token::Type) => self.parse_type_alias(),
             _ => self.parse_expression(),
         }
     }
@@ -215,7 +501,7 @@ impl Parser {
                 self.expect(Token::RParen)?;
 
                 Ok(AstNode::FunctionCall {
-                    name: "new_vector".to_string(),
+                    name: "new_vector".into(),
                     args: vec![],
                 })
             }
@@ -227,7 +513,7 @@ impl Parser {
                 self.expect(Token::RParen)?;
 
                 Ok(AstNode::FunctionCall {
-                    name: "new_hashmap".to_string(),
+                    name: "new_hashmap".into(),
                     args: vec![],
                 })
             }
@@ -237,8 +523,17 @@ impl Parser {
 
     fn parse_variable_declaration(&mut self) -> Result<AstNode, String> {
         self.advance(); // consume 'let'
+        if self.peek() == Some(&Token::LParen) {
+            return self.parse_destructure_declaration();
+        }
+
+        let weak = self.peek() == Some(&Token::WeakAttr);
+        if weak {
+            self.advance(); // consume '#weak'
+        }
+
         let name = match self.advance() {
-            Some(Token::Identifier(name)) => name,
+            Some(Token::Identifier(name)) => Symbol::intern(&name),
             _ => return Err("Expected identifier after 'let'".to_string()),
         };
 
@@ -256,6 +551,13 @@ impl Parser {
             None
         };
 
+        let ownership = match type_annotation {
+            _ if weak => Some(Ownership::Weak),
+            Some(Type::Unique(_)) => Some(Ownership::Unique),
+            Some(Type::Shared(_)) => Some(Ownership::Shared),
+            _ => None,
+        };
+
         match self.peek() {
             Some(Token::Semicolon) => {
                 self.advance(); // Consume semicolon
@@ -263,7 +565,7 @@ impl Parser {
                     name,
                     type_annotation,
                     initializer,
-                    ownership: None,
+                    ownership,
                 })
             }
             other => Err(format!(
@@ -273,10 +575,72 @@ impl Parser {
         }
     }
 
+    fn parse_destructure_declaration(&mut self) -> Result<AstNode, String> {
+        self.expect(Token::LParen)?;
+        let mut names = Vec::new();
+        while self.peek() != Some(&Token::RParen) {
+            if !names.is_empty() {
+                self.expect(Token::Comma)?;
+            }
+            match self.advance() {
+                Some(Token::Identifier(name)) => names.push(Symbol::intern(&name)),
+                other => {
+                    return Err(format!(
+                        "Expected identifier in destructuring pattern, got {:?}",
+                        other
+                    ))
+                }
+            }
+        }
+        self.expect(Token::RParen)?;
+
+        let type_annotation = if self.peek() == Some(&Token::Colon) {
+            self.advance(); // consume ':'
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        self.expect(Token::Assign)?;
+        let initializer = self.parse_expression()?;
+        self.expect(Token::Semicolon)?;
+
+        Ok(AstNode::DestructureDecl {
+            names,
+            type_annotation,
+            initializer: Box::new(initializer),
+        })
+    }
+
     fn parse_type(&mut self) -> Result<Type, String> {
         match self.advance() {
             Some(Token::Tilde) => Ok(Type::Unique(Box::new(self.parse_type()?))),
+            Some(Token::LParen) => {
+                let mut types = Vec::new();
+                while self.peek() != Some(&Token::RParen) {
+                    if !types.is_empty() {
+                        self.expect(Token::Comma)?;
+                    }
+                    types.push(self.parse_type()?);
+                }
+                self.expect(Token::RParen)?;
+                Ok(Type::Tuple(types))
+            }
             Some(Token::At) => Ok(Type::Shared(Box::new(self.parse_type()?))),
+            Some(Token::Vec) => {
+                self.expect(Token::Lt)?;
+                let element = self.parse_type()?;
+                self.expect(Token::Gt)?;
+                Ok(Type::Vec(Box::new(element)))
+            }
+            Some(Token::HashMap) => {
+                self.expect(Token::Lt)?;
+                let key = self.parse_type()?;
+                self.expect(Token::Comma)?;
+                let value = self.parse_type()?;
+                self.expect(Token::Gt)?;
+                Ok(Type::HashMap(Box::new(key), Box::new(value)))
+            }
             Some(token) => match token {
                 Token::TypeI8 => Ok(Type::I8),
                 Token::TypeI16 => Ok(Type::I16),
@@ -291,6 +655,9 @@ impl Parser {
                 Token::TypeBool => Ok(Type::Bool),
                 Token::TypeString => Ok(Type::String),
                 Token::TypeDyn => Ok(Type::Dynamic),
+                Token::Identifier(name) => {
+                    self.expand_alias(Symbol::intern(&name), &mut Vec::new())
+                }
                 _ => Err(format!("Unexpected type token: {:?}", token)),
             },
             None => Err("Unexpected end of input while parsing type".to_string()),
@@ -298,10 +665,14 @@ impl Parser {
     }
 
     fn parse_function_declaration(&mut self) -> Result<AstNode, String> {
+        let mut is_async = false;
+        if self.peek() == Some(&Token::Async) {
+            self.advance(); // consume 'async'
+            is_async = true;
+        }
         self.advance(); // consume 'func'
 
         let mut attributes = Vec::new();
-        let mut is_async = false;
 
         while let Some(token) = self.peek() {
             match token {
@@ -330,24 +701,74 @@ impl Parser {
         }
 
         let name = match self.advance() {
-            Some(Token::Identifier(name)) => name,
+            Some(Token::Identifier(name)) => Symbol::intern(&name),
             _ => return Err("Expected function name".to_string()),
         };
 
+        let (params, defaults, return_type) = self.parse_signature()?;
+        let body = self.parse_block()?;
+
+        Ok(AstNode::FunctionDecl {
+            name,
+            params,
+            defaults,
+            return_type,
+            body: Box::new(body),
+            attributes,
+            is_async,
+        })
+    }
+
+    // Parses `(name: type, name: type = default, name: ...type) -> type`,
+    // shared by named and anonymous functions. Only trailing parameters can
+    // have defaults, and only the last can take the rest of the arguments.
+    fn parse_signature(&mut self) -> Result<Signature, String> {
         self.expect(Token::LParen)?;
         let mut params = Vec::new();
+        let mut defaults = Vec::new();
         while self.peek() != Some(&Token::RParen) {
             if !params.is_empty() {
                 self.expect(Token::Comma)?;
+                // Allow a trailing comma
+                if self.peek() == Some(&Token::RParen) {
+                    break;
+                }
+            }
+            if let Some((rest, Type::Rest(_))) = params.last() {
+                return Err(format!(
+                    "Rest parameter {} must be the last parameter",
+                    rest
+                ));
             }
 
             let param_name = match self.advance() {
-                Some(Token::Identifier(name)) => name,
+                Some(Token::Identifier(name)) => Symbol::intern(&name),
                 _ => return Err("Expected parameter name".to_string()),
             };
 
             self.expect(Token::Colon)?;
+            if self.peek() == Some(&Token::Ellipsis) {
+                self.advance();
+                let element = self.parse_type()?;
+                if self.peek() == Some(&Token::Assign) {
+                    return Err(format!(
+                        "Rest parameter {} can't have a default value",
+                        param_name
+                    ));
+                }
+                params.push((param_name, Type::Rest(Box::new(element))));
+                continue;
+            }
             let param_type = self.parse_type()?;
+            if self.peek() == Some(&Token::Assign) {
+                self.advance();
+                defaults.push(self.parse_expression()?);
+            } else if !defaults.is_empty() {
+                return Err(format!(
+                    "Parameter {} needs a default value, since an earlier parameter has one",
+                    param_name
+                ));
+            }
             params.push((param_name, param_type));
         }
         self.expect(Token::RParen)?;
@@ -359,18 +780,89 @@ impl Parser {
             None
         };
 
-        let body = self.parse_block()?;
+        Ok((params, defaults, return_type))
+    }
 
-        Ok(AstNode::FunctionDecl {
+    // A positional argument, or `name: value` once the next two tokens are an
+    // identifier and a colon
+    fn parse_argument(&mut self, previous: &[AstNode]) -> Result<AstNode, String> {
+        let name = match (self.peek(), self.tokens.get(self.current + 1)) {
+            (Some(Token::Identifier(name)), Some(Token::Colon)) => Symbol::intern(name),
+            _ => {
+                if matches!(previous.last(), Some(AstNode::NamedArgument { .. })) {
+                    return Err("Positional argument after a named argument".to_string());
+                }
+                return self.parse_expression();
+            }
+        };
+        self.advance(); // consume name
+        self.advance(); // consume ':'
+        Ok(AstNode::NamedArgument {
             name,
+            value: Box::new(self.parse_expression()?),
+        })
+    }
+
+    // Each `${...}` in an interpolated string holds exactly one expression
+    fn parse_interpolation(&self, parts: Vec<StringPart>) -> Result<AstNode, String> {
+        let mut nodes = Vec::new();
+        for part in parts {
+            match part {
+                StringPart::Text(text) if text.is_empty() => {}
+                StringPart::Text(text) => nodes.push(AstNode::String(text)),
+                StringPart::Code(tokens) => {
+                    if tokens.is_empty() {
+                        return Err("Empty ${} in string".to_string());
+                    }
+                    let mut parser = Parser::new(tokens);
+                    parser.aliases = self.aliases.clone();
+                    nodes.push(parser.parse_expression()?);
+                    if let Some(token) = parser.peek() {
+                        return Err(format!("Unexpected {:?} in ${{}} in string", token));
+                    }
+                }
+            }
+        }
+        Ok(AstNode::Interpolation(nodes))
+    }
+
+    fn parse_anonymous_function(&mut self) -> Result<AstNode, String> {
+        self.advance(); // consume 'func'
+        let (params, defaults, return_type) = self.parse_signature()?;
+        let body = self.parse_block()?;
+
+        Ok(AstNode::Lambda {
             params,
+            defaults,
             return_type,
             body: Box::new(body),
-            attributes,
-            is_async,
         })
     }
 
+    // `channel()`, `send(channel, value)` and `recv(channel)` are keywords
+    // rather than builtins, so they can't be shadowed
+    fn parse_channel_operation(&mut self) -> Result<AstNode, String> {
+        let keyword = self.advance();
+        self.expect(Token::LParen)?;
+        let node = match keyword {
+            Some(Token::Send) => {
+                let channel = self.parse_expression()?;
+                self.expect(Token::Comma)?;
+                let value = self.parse_expression()?;
+                AstNode::Send {
+                    channel: Box::new(channel),
+                    value: Box::new(value),
+                }
+            }
+            Some(Token::Recv) => AstNode::Receive {
+                channel: Box::new(self.parse_expression()?),
+            },
+            _ => AstNode::ChannelCreate,
+        };
+        self.expect(Token::RParen)?;
+        Ok(node)
+    }
+
     fn parse_if_statement(&mut self) -> Result<AstNode, String> {
         self.advance(); // consume 'if'
 
@@ -411,6 +903,243 @@ impl Parser {
         })
     }
 
+    fn parse_do_while_statement(&mut self) -> Result<AstNode, String> {
+        self.advance(); // consume 'do'
+        let body = self.parse_block()?;
+        self.expect(Token::While)?;
+        let condition = self.parse_expression()?;
+
+        Ok(AstNode::DoWhile {
+            body: Box::new(body),
+            condition: Box::new(condition),
+        })
+    }
+
+    fn parse_match_expression(&mut self) -> Result<AstNode, String> {
+        self.advance(); // consume 'match'
+
+        let scrutinee = self.parse_expression()?;
+        self.expect(Token::LBrace)?;
+
+        let mut arms = Vec::new();
+        while self.peek() != Some(&Token::RBrace) {
+            let pattern = self.parse_pattern()?;
+            self.expect(Token::FatArrow)?;
+            let body = if self.peek() == Some(&Token::LBrace) {
+                self.parse_block()?
+            } else {
+                self.parse_expression()?
+            };
+            arms.push(MatchArm { pattern, body });
+
+            if self.peek() == Some(&Token::Comma) {
+                self.advance();
+            } else if self.peek() != Some(&Token::RBrace) {
+                return Err(format!(
+                    "Expected ',' or '}}' after match arm, got {:?}",
+                    self.peek()
+                ));
+            }
+        }
+        self.expect(Token::RBrace)?;
+
+        Ok(AstNode::Match {
+            scrutinee: Box::new(scrutinee),
+            arms,
+        })
+    }
+
+    fn parse_try_expression(&mut self) -> Result<AstNode, String> {
+        self.advance(); // consume 'try'
+
+        let body = self.parse_block()?;
+        self.expect(Token::Else)?;
+        self.expect(Token::LParen)?;
+        let binding = match self.advance() {
+            Some(Token::Identifier(name)) => Symbol::intern(&name),
+            other => return Err(format!("Expected error name after 'else', got {:?}", other)),
+        };
+        self.expect(Token::RParen)?;
+        let handler = self.parse_block()?;
+
+        Ok(AstNode::Try {
+            body: Box::new(body),
+            binding,
+            handler: Box::new(handler),
+        })
+    }
+
+    // `type Name = type;`. Later annotations naming the alias get the type it
+    // stands for.
+    fn parse_type_alias(&mut self) -> Result<AstNode, String> {
+        self.advance(); // consume 'type'
+        let name = match self.advance() {
+            Some(Token::Identifier(name)) => Symbol::intern(&name),
+            _ => return Err("Expected type alias name".to_string()),
+        };
+        self.expect(Token::Assign)?;
+        let ty = self.parse_type()?;
+
+        let previous = self.aliases.insert(name, ty);
+        match self.expand_alias(name, &mut Vec::new()) {
+            Ok(ty) => Ok(AstNode::TypeAlias { name, ty }),
+            Err(e) => {
+                match previous {
+                    Some(previous) => self.aliases.insert(name, previous),
+                    None => self.aliases.remove(&name),
+                };
+                Err(e)
+            }
+        }
+    }
+
+    // The type `name` stands for, following aliases of aliases. A name that
+    // isn't an alias is left for the interpreter, as an enum.
+    fn expand_alias(&self, name: Symbol, expanding: &mut Vec<Symbol>) -> Result<Type, String> {
+        let Some(ty) = self.aliases.get(&name) else {
+            return Ok(Type::Named(name));
+        };
+        if expanding.contains(&name) {
+            return Err(format!("Type alias {} refers to itself", name));
+        }
+        expanding.push(name);
+        let expanded = self.expand_type(ty.clone(), expanding);
+        expanding.pop();
+        expanded
+    }
+
+    fn expand_type(&self, ty: Type, expanding: &mut Vec<Symbol>) -> Result<Type, String> {
+        let mut expand = |inner: Box<Type>| -> Result<Box<Type>, String> {
+            Ok(Box::new(self.expand_type(*inner, expanding)?))
+        };
+        Ok(match ty {
+            Type::Named(name) => return self.expand_alias(name, expanding),
+            Type::Unique(inner) => Type::Unique(expand(inner)?),
+            Type::Shared(inner) => Type::Shared(expand(inner)?),
+            Type::Vec(inner) => Type::Vec(expand(inner)?),
+            Type::Rest(inner) => Type::Rest(expand(inner)?),
+            Type::HashMap(key, value) => Type::HashMap(expand(key)?, expand(value)?),
+            Type::Tuple(types) => Type::Tuple(
+                types
+                    .into_iter()
+                    .map(|ty| self.expand_type(ty, expanding))
+                    .collect::<Result<_, _>>()?,
+            ),
+            other => other,
+        })
+    }
+
+    // `enum Name { Variant, Variant(type), ... }`
+    fn parse_enum_declaration(&mut self) -> Result<AstNode, String> {
+        self.advance(); // consume 'enum'
+        let name = match self.advance() {
+            Some(Token::Identifier(name)) => Symbol::intern(&name),
+            _ => return Err("Expected enum name".to_string()),
+        };
+        self.expect(Token::LBrace)?;
+
+        let mut variants: Vec<Variant> = Vec::new();
+        while self.peek() != Some(&Token::RBrace) {
+            let variant = match self.advance() {
+                Some(Token::Identifier(variant)) => Symbol::intern(&variant),
+                other => return Err(format!("Expected variant name, got {:?}", other)),
+            };
+            if variants.iter().any(|(existing, _)| *existing == variant) {
+                return Err(format!("Enum {} declares {} twice", name, variant));
+            }
+            let payload = if self.peek() == Some(&Token::LParen) {
+                self.advance();
+                let ty = self.parse_type()?;
+                self.expect(Token::RParen)?;
+                Some(ty)
+            } else {
+                None
+            };
+            variants.push((variant, payload));
+
+            if self.peek() == Some(&Token::Comma) {
+                self.advance();
+            } else if self.peek() != Some(&Token::RBrace) {
+                return Err(format!(
+                    "Expected ',' or '}}' after enum variant, got {:?}",
+                    self.peek()
+                ));
+            }
+        }
+        self.expect(Token::RBrace)?;
+
+        Ok(AstNode::EnumDecl { name, variants })
+    }
+
+    // `Enum::Variant`, with a parenthesized payload for variants that take one
+    fn parse_enum_variant(&mut self, enum_name: Symbol) -> Result<AstNode, String> {
+        self.expect(Token::DoubleColon)?;
+        let variant = match self.advance() {
+            Some(Token::Identifier(variant)) => Symbol::intern(&variant),
+            other => return Err(format!("Expected variant name, got {:?}", other)),
+        };
+        let payload = if self.peek() == Some(&Token::LParen) {
+            self.advance();
+            let payload = self.parse_expression()?;
+            self.expect(Token::RParen)?;
+            Some(Box::new(payload))
+        } else {
+            None
+        };
+
+        Ok(AstNode::EnumVariant {
+            enum_name,
+            variant,
+            payload,
+        })
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, String> {
+        match self.advance() {
+            Some(Token::Identifier(name)) if name == "_" => Ok(Pattern::Wildcard),
+            Some(Token::Identifier(name)) if self.peek() == Some(&Token::DoubleColon) => {
+                self.advance(); // consume '::'
+                let variant = match self.advance() {
+                    Some(Token::Identifier(variant)) => Symbol::intern(&variant),
+                    other => return Err(format!("Expected variant name, got {:?}", other)),
+                };
+                let binding = if self.peek() == Some(&Token::LParen) {
+                    self.advance();
+                    let binding = match self.advance() {
+                        Some(Token::Identifier(binding)) if binding == "_" => None,
+                        Some(Token::Identifier(binding)) => Some(Symbol::intern(&binding)),
+                        other => return Err(format!("Expected a name to bind, got {:?}", other)),
+                    };
+                    self.expect(Token::RParen)?;
+                    binding
+                } else {
+                    None
+                };
+                Ok(Pattern::Variant {
+                    enum_name: Symbol::intern(&name),
+                    variant,
+                    binding,
+                })
+            }
+            Some(Token::Identifier(name)) if name == "true" || name == "false" => {
+                Ok(Pattern::Literal(AstNode::Boolean(name == "true")))
+            }
+            Some(Token::Integer(i)) => Ok(Pattern::Literal(AstNode::Integer(i))),
+            Some(Token::Float(f)) => Ok(Pattern::Literal(AstNode::Float(f))),
+            Some(Token::String(s)) => Ok(Pattern::Literal(AstNode::String(s))),
+            Some(Token::Bool(b)) => Ok(Pattern::Literal(AstNode::Boolean(b))),
+            Some(Token::Minus) => match self.advance() {
+                Some(Token::Integer(i)) => Ok(Pattern::Literal(AstNode::Integer(-i))),
+                Some(Token::Float(f)) => Ok(Pattern::Literal(AstNode::Float(-f))),
+                other => Err(format!(
+                    "Expected number after '-' in pattern, got {:?}",
+                    other
+                )),
+            },
+            other => Err(format!("Unsupported match pattern: {:?}", other)),
+        }
+    }
+
     fn parse_expression(&mut self) -> Result<AstNode, String> {
         let expr = self.parse_logical_or()?;
 
@@ -453,6 +1182,33 @@ impl Parser {
                     value: Box::new(value),
                 })
             }
+            Some(Token::StarEq) => {
+                self.advance();
+                let value = self.parse_expression()?;
+                Ok(AstNode::CompoundAssign {
+                    operator: Operator::SelfMul,
+                    target: Box::new(expr),
+                    value: Box::new(value),
+                })
+            }
+            Some(Token::SlashEq) => {
+                self.advance();
+                let value = self.parse_expression()?;
+                Ok(AstNode::CompoundAssign {
+                    operator: Operator::SelfDiv,
+                    target: Box::new(expr),
+                    value: Box::new(value),
+                })
+            }
+            Some(Token::PercentEq) => {
+                self.advance();
+                let value = self.parse_expression()?;
+                Ok(AstNode::CompoundAssign {
+                    operator: Operator::SelfMod,
+                    target: Box::new(expr),
+                    value: Box::new(value),
+                })
+            }
             Some(Token::MinusMinus) => {
                 self.advance();
                 Ok(AstNode::UnaryOp {
@@ -526,6 +1282,7 @@ impl Parser {
                 Token::Gt => Operator::Gt,
                 Token::LtEq => Operator::LtEq,
                 Token::GtEq => Operator::GtEq,
+                Token::In => Operator::In,
                 _ => break,
             };
             self.advance();
@@ -601,6 +1358,21 @@ impl Parser {
                     operand: Box::new(operand),
                 })
             }
+            Some(Token::Tilde) => {
+                self.advance();
+                let operand = self.parse_unary()?;
+                Ok(AstNode::UnaryOp {
+                    operator: UnaryOperator::BitNot,
+                    operand: Box::new(operand),
+                })
+            }
+            Some(Token::Await) => {
+                self.advance();
+                let expression = self.parse_unary()?;
+                Ok(AstNode::Await {
+                    expression: Box::new(expression),
+                })
+            }
             _ => self.parse_primary(),
         }
     }
@@ -634,6 +1406,10 @@ impl Parser {
                         Err("Expected string".to_string())
                     }
                 }
+                Token::Interpolated(parts) => {
+                    self.advance();
+                    self.parse_interpolation(parts)
+                }
                 Token::Bool(_) => {
                     if let Some(Token::Bool(value)) = self.advance() {
                         Ok(AstNode::Boolean(value))
@@ -642,17 +1418,27 @@ impl Parser {
                     }
                 }
                 Token::Vec | Token::HashMap => self.parse_collection_creation(),
+                Token::Match => self.parse_match_expression(),
+                Token::Try => self.parse_try_expression(),
+                Token::Func => self.parse_anonymous_function(),
+                Token::Channel | Token::Send | Token::Recv => self.parse_channel_operation(),
                 Token::Identifier(name) => {
                     self.advance(); // consume identifier
-                    if self.peek() == Some(&Token::LParen) || StdLib::is_builtin(&name) {
-                        // Handle function call for both user-defined and built-in functions
+                    let name = Symbol::intern(&name);
+                    if self.peek() == Some(&Token::LParen) {
+                        // Builtins parse like any other call; the interpreter
+                        // decides whether a user definition shadows one
                         self.advance(); // consume '('
                         let mut arguments = Vec::new();
                         while self.peek() != Some(&Token::RParen) {
                             if !arguments.is_empty() {
                                 self.expect(Token::Comma)?;
+                                // Allow a trailing comma
+                                if self.peek() == Some(&Token::RParen) {
+                                    break;
+                                }
                             }
-                            arguments.push(self.parse_expression()?);
+                            arguments.push(self.parse_argument(&arguments)?);
                         }
                         self.expect(Token::RParen)?;
 
@@ -666,38 +1452,79 @@ impl Parser {
                             args: arguments,
                         })
                     } else if self.peek() == Some(&Token::DoubleColon) {
-                        // Handle static method calls like Vec::new()
-                        self.parse_collection_creation()
+                        self.parse_enum_variant(name)
                     } else {
                         Ok(AstNode::Identifier(name))
                     }
                 }
                 Token::LParen => {
                     self.advance(); // consume '('
+                    if self.peek() == Some(&Token::RParen) {
+                        self.advance();
+                        return Ok(AstNode::Tuple(vec![]));
+                    }
+
                     let expr = self.parse_expression()?;
-                    self.expect(Token::RParen)?;
-                    Ok(expr)
+                    if self.peek() != Some(&Token::Comma) {
+                        self.expect(Token::RParen)?;
+                        Ok(expr)
+                    } else {
+                        // A comma makes this a tuple rather than a grouping
+                        let mut elements = vec![expr];
+                        while self.peek() == Some(&Token::Comma) {
+                            self.advance();
+                            if self.peek() == Some(&Token::RParen) {
+                                break;
+                            }
+                            elements.push(self.parse_expression()?);
+                        }
+                        self.expect(Token::RParen)?;
+                        Ok(AstNode::Tuple(elements))
+                    }
                 }
                 token => {
                     self.advance();
-                    Err(format!(
-                        "Unexpected token in primary expression: {:?}",
-                        token
-                    ))
+                    match unsupported_keyword(&token) {
+                        Some(keyword) => Err(format!(
+                            "Unexpected keyword '{}': reserved but not supported yet",
+                            keyword
+                        )),
+                        None => Err(format!(
+                            "Unexpected token in primary expression: {:?}",
+                            token
+                        )),
+                    }
                 }
             },
             None => return Err("Unexpected end of input".to_string()),
         }?;
 
-        while self.peek() == Some(&Token::LBracket) {
-            self.advance(); // consume '['
-            let index = self.parse_expression()?;
-            self.expect(Token::RBracket)?;
-
-            expr = AstNode::IndexAccess {
-                target: Box::new(expr),
-                index: Box::new(index),
-            };
+        loop {
+            match self.peek() {
+                Some(Token::LBracket) => {
+                    self.advance(); // consume '['
+                    let index = self.parse_expression()?;
+                    self.expect(Token::RBracket)?;
+
+                    expr = AstNode::IndexAccess {
+                        target: Box::new(expr),
+                        index: Box::new(index),
+                    };
+                }
+                Some(Token::Dot) => {
+                    self.advance(); // consume '.'
+                    let index = match self.advance() {
+                        Some(Token::Integer(i)) if i >= 0 => i as usize,
+                        other => return Err(format!("Expected tuple index, got {:?}", other)),
+                    };
+
+                    expr = AstNode::TupleAccess {
+                        target: Box::new(expr),
+                        index,
+                    };
+                }
+                _ => break,
+            }
         }
 
         Ok(expr)
@@ -706,13 +1533,28 @@ impl Parser {
     fn parse_block(&mut self) -> Result<AstNode, String> {
         self.expect(Token::LBrace)?;
         let mut statements = Vec::new();
+        let mut discards_value = false;
+        // Lines are marked again inside the block, so a one-line body still
+        // pauses when called
+        let outer_breakpoint = self.last_breakpoint.take();
 
         while self.peek() != Some(&Token::RBrace) {
-            statements.push(self.parse_statement()?);
+            let start = self.current;
+            let statement = self.parse_statement_body()?;
+            statements.extend(self.breakpoint_marker(start));
+            statements.push(statement);
+            discards_value = self.peek() == Some(&Token::Semicolon);
+            if discards_value {
+                self.advance();
+            }
         }
 
         self.expect(Token::RBrace)?;
-        Ok(AstNode::Block(statements))
+        self.last_breakpoint = outer_breakpoint;
+        Ok(AstNode::Block {
+            statements,
+            discards_value,
+        })
     }
 }
 
@@ -778,4 +1620,307 @@ mod tests {
         //     panic!("Expected function declaration");
         // }
     }
+
+    #[test]
+    fn test_parse_recovering_reports_all_errors() {
+        let input = "let x: i32 = ;\nlet y = 1;\nlet z: i32 = );\nlet w = 2;";
+        let mut lexer = Lexer::new(input.to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if matches!(token, Token::Eof) {
+                break;
+            }
+            tokens.push(token);
+        }
+
+        let errors = Parser::new(tokens.clone()).parse_recovering().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("Semicolon"));
+        assert!(errors[1].contains("RParen"));
+
+        // The fail-fast parser stops at the first error
+        assert!(Parser::new(tokens).parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_match() {
+        let input = r#"match x { 1 => "one", -2 => { "minus two" }, _ => "other" }"#;
+        let mut lexer = Lexer::new(input.to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if matches!(token, Token::Eof) {
+                break;
+            }
+            tokens.push(token);
+        }
+
+        let ast = Parser::new(tokens).parse().unwrap();
+        match &ast[0] {
+            AstNode::Match { arms, .. } => {
+                assert_eq!(arms.len(), 3);
+                assert_eq!(arms[0].pattern, Pattern::Literal(AstNode::Integer(1)));
+                assert_eq!(arms[1].pattern, Pattern::Literal(AstNode::Integer(-2)));
+                assert_eq!(arms[2].pattern, Pattern::Wildcard);
+            }
+            other => panic!("Expected match expression, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_tuples() {
+        let input = "func divmod(a: i32, b: i32) -> (i32, i32) { (a / b, a % b) }
+            let (q, r) = divmod(7, 2);
+            let first = (1, 2).0;
+            let grouped = (1 + 2);";
+        let mut lexer = Lexer::new(input.to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if matches!(token, Token::Eof) {
+                break;
+            }
+            tokens.push(token);
+        }
+
+        let ast = Parser::new(tokens).parse().unwrap();
+        match &ast[0] {
+            AstNode::FunctionDecl { return_type, .. } => {
+                assert_eq!(return_type, &Some(Type::Tuple(vec![Type::I32, Type::I32])));
+            }
+            other => panic!("Expected function declaration, got {:?}", other),
+        }
+        assert!(matches!(
+            &ast[1],
+            AstNode::DestructureDecl { names, .. } if names == &["q".into(), "r".into()]
+        ));
+        assert!(matches!(
+            &ast[2],
+            AstNode::VariableDecl { initializer: Some(init), .. }
+                if matches!(init.as_ref(), AstNode::TupleAccess { index: 0, .. })
+        ));
+        assert!(matches!(
+            &ast[3],
+            AstNode::VariableDecl { initializer: Some(init), .. }
+                if matches!(init.as_ref(), AstNode::BinaryOp { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_try() {
+        let mut lexer = Lexer::new("try { risky() } else (err) { 0 }".to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if matches!(token, Token::Eof) {
+                break;
+            }
+            tokens.push(token);
+        }
+
+        let ast = Parser::new(tokens).parse().unwrap();
+        assert!(matches!(
+            &ast[0],
+            AstNode::Try { binding, .. } if binding.as_str() == "err"
+        ));
+
+        for input in [
+            "try { 1 }",
+            "try { 1 } else { 0 }",
+            "try { 1 } else (1) { 0 }",
+        ] {
+            let mut lexer = Lexer::new(input.to_string());
+            let mut tokens = Vec::new();
+            loop {
+                let token = lexer.next_token();
+                if matches!(token, Token::Eof) {
+                    break;
+                }
+                tokens.push(token);
+            }
+            assert!(Parser::new(tokens).parse().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_do_while() {
+        let ast = crate::parse("do { x += 1 } while x < 3;").unwrap();
+        assert!(matches!(
+            &ast[..],
+            [AstNode::DoWhile { body, condition }]
+                if matches!(body.as_ref(), AstNode::Block { .. })
+                    && matches!(condition.as_ref(), AstNode::BinaryOp { .. })
+        ));
+        assert!(crate::parse("do { x += 1 }").is_err());
+    }
+
+    #[test]
+    fn test_parse_block_trailing_semicolon() {
+        let ast = crate::parse("if true { 5; } else { 5 }").unwrap();
+        let [AstNode::IfExpr {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        }] = &ast[..]
+        else {
+            panic!("expected an if expression, got {:?}", ast);
+        };
+        assert_eq!(
+            then_branch.as_ref(),
+            &AstNode::Block {
+                statements: vec![AstNode::Integer(5)],
+                discards_value: true,
+            }
+        );
+        assert_eq!(
+            else_branch.as_ref(),
+            &AstNode::Block {
+                statements: vec![AstNode::Integer(5)],
+                discards_value: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_breakpoint_markers() {
+        let source = "let a = 1; let b = 2;\nwhile a < b {\n    a += 1;\n}";
+        let ast = crate::parse_with_breakpoints(source, &HashSet::from([1, 3])).unwrap();
+        // Only the first statement on a line is marked
+        assert_eq!(ast.len(), 4);
+        assert_eq!(ast[0], AstNode::Breakpoint(1));
+        let AstNode::WhileLoop { body, .. } = &ast[3] else {
+            panic!("expected a while loop, got {:?}", ast[3]);
+        };
+        assert!(matches!(
+            body.as_ref(),
+            AstNode::Block { statements, .. } if statements[0] == AstNode::Breakpoint(3)
+        ));
+        assert_eq!(crate::parse(source).unwrap().len(), 3);
+
+        // A one-line body is marked as well as the declaration around it
+        let ast = crate::parse_with_breakpoints("func f() { 1 }", &HashSet::from([1])).unwrap();
+        let [AstNode::Breakpoint(1), AstNode::FunctionDecl { body, .. }] = &ast[..] else {
+            panic!("expected a marked function, got {:?}", ast);
+        };
+        assert!(matches!(
+            body.as_ref(),
+            AstNode::Block { statements, .. } if statements[0] == AstNode::Breakpoint(1)
+        ));
+
+        // A statement continued onto a breakpoint line is marked for it
+        let ast = crate::parse_with_breakpoints("let a = (1,\n2);", &HashSet::from([2])).unwrap();
+        assert_eq!(ast[0], AstNode::Breakpoint(2));
+    }
+
+    #[test]
+    fn test_parse_type_aliases() {
+        let input = "type Row = Vec<Cell>;
+            type Cell = i32;
+            type Grid = (Row, ~Row);
+            let g: Grid = (Vec::new(), Vec::new());
+            type Loop = Other;
+            type Other = Loop;";
+        let mut lexer = Lexer::new(input.to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if matches!(token, Token::Eof) {
+                break;
+            }
+            tokens.push(token);
+        }
+
+        let row = Type::Vec(Box::new(Type::I32));
+        let grid = Type::Tuple(vec![row.clone(), Type::Unique(Box::new(row))]);
+        let mut parser = Parser::new(tokens);
+        let mut statements = Vec::new();
+        while parser.peek().is_some() {
+            statements.push(parser.parse_statement());
+        }
+        assert!(matches!(
+            &statements[2],
+            Ok(AstNode::TypeAlias { ty, .. }) if *ty == grid
+        ));
+        assert!(matches!(
+            &statements[3],
+            Ok(AstNode::VariableDecl { type_annotation: Some(ty), .. }) if *ty == grid
+        ));
+        assert_eq!(
+            statements[5],
+            Err("Type alias Other refers to itself".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_generic_types() {
+        let input = "Vec<i32> HashMap<string, i32> Vec<Vec<i32>> HashMap<string, Vec<@i32>>";
+        let mut lexer = Lexer::new(input.to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if matches!(token, Token::Eof) {
+                break;
+            }
+            tokens.push(token);
+        }
+
+        let ints = Type::Vec(Box::new(Type::I32));
+        let mut parser = Parser::new(tokens);
+        assert_eq!(parser.parse_type(), Ok(ints.clone()));
+        assert_eq!(
+            parser.parse_type(),
+            Ok(Type::HashMap(Box::new(Type::String), Box::new(Type::I32)))
+        );
+        assert_eq!(parser.parse_type(), Ok(Type::Vec(Box::new(ints))));
+        assert_eq!(
+            parser.parse_type(),
+            Ok(Type::HashMap(
+                Box::new(Type::String),
+                Box::new(Type::Vec(Box::new(Type::Shared(Box::new(Type::I32)))))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_trailing_commas() {
+        let input = "func f(x: i32, y: i32,) { x }
+            func g(items: ...i32,) { items }
+            f(1, 2,);
+            f(1, y: 2,);
+            f(,);";
+        let mut lexer = Lexer::new(input.to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if matches!(token, Token::Eof) {
+                break;
+            }
+            tokens.push(token);
+        }
+
+        let mut parser = Parser::new(tokens);
+        let mut statements = Vec::new();
+        while parser.peek().is_some() {
+            statements.push(parser.parse_statement());
+        }
+        assert!(matches!(
+            &statements[0],
+            Ok(AstNode::FunctionDecl { params, .. }) if params.len() == 2
+        ));
+        assert!(matches!(
+            &statements[1],
+            Ok(AstNode::FunctionDecl { params, .. }) if params.len() == 1
+        ));
+        assert!(matches!(
+            &statements[2],
+            Ok(AstNode::FunctionCall { args, .. }) if args.len() == 2
+        ));
+        assert!(matches!(
+            &statements[3],
+            Ok(AstNode::FunctionCall { args, .. })
+                if matches!(args[1], AstNode::NamedArgument { .. })
+        ));
+        assert!(statements[4].is_err());
+    }
 }
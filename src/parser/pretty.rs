@@ -0,0 +1,295 @@
+use super::{AstNode, Param, Pattern, Type};
+use std::fmt::Write;
+
+// Renders nodes as an indented tree, one node per line with its children
+// indented beneath it. Used by `aki --ast`.
+pub fn pretty_print(nodes: &[AstNode]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        write_node(&mut out, node, 0);
+    }
+    out
+}
+
+fn params_label(params: &[Param], return_type: &Option<Type>) -> String {
+    let params = params
+        .iter()
+        .map(|(name, ty)| format!("{}: {:?}", name, ty))
+        .collect::<Vec<_>>()
+        .join(", ");
+    match return_type {
+        Some(ty) => format!("({}) -> {:?}", params, ty),
+        None => format!("({})", params),
+    }
+}
+
+fn annotation_label(type_annotation: &Option<Type>) -> String {
+    type_annotation
+        .as_ref()
+        .map_or(String::new(), |ty| format!(": {:?}", ty))
+}
+
+// The text on a node's own line, without its children
+fn label(node: &AstNode) -> String {
+    match node {
+        AstNode::Integer(n) => format!("Integer {}", n),
+        AstNode::Float(f) => format!("Float {}", f),
+        AstNode::String(s) => format!("String {:?}", s),
+        AstNode::Boolean(b) => format!("Boolean {}", b),
+        AstNode::Interpolation(_) => "Interpolation".to_string(),
+        AstNode::Identifier(name) => format!("Identifier {}", name),
+        AstNode::VariableDecl {
+            name,
+            type_annotation,
+            ownership,
+            ..
+        } => {
+            let ownership = ownership
+                .as_ref()
+                .map_or(String::new(), |o| format!(" {:?}", o));
+            format!(
+                "Let {}{}{}",
+                name,
+                annotation_label(type_annotation),
+                ownership
+            )
+        }
+        AstNode::DestructureDecl {
+            names,
+            type_annotation,
+            ..
+        } => format!(
+            "Let ({}){}",
+            names
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            annotation_label(type_annotation)
+        ),
+        AstNode::FunctionDecl {
+            name,
+            params,
+            return_type,
+            attributes,
+            is_async,
+            ..
+        } => {
+            let mut label = String::new();
+            for attribute in attributes {
+                let _ = write!(label, "#{:?} ", attribute);
+            }
+            if *is_async {
+                label.push_str("async ");
+            }
+            format!(
+                "{}Func {}{}",
+                label,
+                name,
+                params_label(params, return_type)
+            )
+        }
+        AstNode::FunctionCall { name, .. } => format!("Call {}", name),
+        AstNode::NamedArgument { name, .. } => format!("Named {}", name),
+        AstNode::EnumDecl { name, variants } => {
+            let variants = variants
+                .iter()
+                .map(|(variant, payload)| match payload {
+                    Some(ty) => format!("{}({:?})", variant, ty),
+                    None => variant.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("Enum {} {{ {} }}", name, variants)
+        }
+        AstNode::EnumVariant {
+            enum_name, variant, ..
+        } => format!("Variant {}::{}", enum_name, variant),
+        AstNode::Lambda {
+            params,
+            return_type,
+            ..
+        } => format!("Lambda {}", params_label(params, return_type)),
+        AstNode::IndexAccess { .. } => "Index".to_string(),
+        AstNode::Tuple(_) => "Tuple".to_string(),
+        AstNode::TupleAccess { index, .. } => format!("TupleAccess .{}", index),
+        AstNode::TypeAnnotation(ty) => format!("Type {:?}", ty),
+        AstNode::TypeAlias { name, ty } => format!("TypeAlias {} = {:?}", name, ty),
+        AstNode::Ownership(ownership) => format!("Ownership {:?}", ownership),
+        AstNode::Block {
+            discards_value: true,
+            ..
+        } => "Block discarding value".to_string(),
+        AstNode::Block { .. } => "Block".to_string(),
+        AstNode::IfExpr { .. } => "If".to_string(),
+        AstNode::WhileLoop { .. } => "While".to_string(),
+        AstNode::DoWhile { .. } => "DoWhile".to_string(),
+        AstNode::Match { .. } => "Match".to_string(),
+        AstNode::Try { binding, .. } => format!("Try else {}", binding),
+        AstNode::Breakpoint(line) => format!("Breakpoint line {}", line),
+        AstNode::BinaryOp { operator, .. } => format!("BinaryOp {:?}", operator),
+        AstNode::UnaryOp { operator, .. } => format!("UnaryOp {:?}", operator),
+        AstNode::CompoundAssign { operator, .. } => format!("CompoundAssign {:?}", operator),
+        AstNode::ChannelCreate => "ChannelCreate".to_string(),
+        AstNode::Send { .. } => "Send".to_string(),
+        AstNode::Receive { .. } => "Receive".to_string(),
+        AstNode::Await { .. } => "Await".to_string(),
+    }
+}
+
+fn write_node(out: &mut String, node: &AstNode, depth: usize) {
+    let _ = writeln!(out, "{}{}", "  ".repeat(depth), label(node));
+    let depth = depth + 1;
+
+    match node {
+        AstNode::VariableDecl {
+            initializer: Some(child),
+            ..
+        }
+        | AstNode::DestructureDecl {
+            initializer: child, ..
+        }
+        | AstNode::NamedArgument { value: child, .. }
+        | AstNode::EnumVariant {
+            payload: Some(child),
+            ..
+        }
+        | AstNode::TupleAccess { target: child, .. }
+        | AstNode::UnaryOp { operand: child, .. }
+        | AstNode::Receive { channel: child }
+        | AstNode::Await { expression: child } => write_node(out, child, depth),
+        AstNode::FunctionCall { args: children, .. }
+        | AstNode::Interpolation(children)
+        | AstNode::Tuple(children)
+        | AstNode::Block {
+            statements: children,
+            ..
+        } => {
+            for child in children {
+                write_node(out, child, depth);
+            }
+        }
+        AstNode::IndexAccess {
+            target: first,
+            index: second,
+        }
+        | AstNode::WhileLoop {
+            condition: first,
+            body: second,
+        }
+        | AstNode::DoWhile {
+            body: first,
+            condition: second,
+        }
+        | AstNode::BinaryOp {
+            left: first,
+            right: second,
+            ..
+        }
+        | AstNode::CompoundAssign {
+            target: first,
+            value: second,
+            ..
+        }
+        | AstNode::Send {
+            channel: first,
+            value: second,
+        } => {
+            write_node(out, first, depth);
+            write_node(out, second, depth);
+        }
+        AstNode::FunctionDecl {
+            params,
+            defaults,
+            body,
+            ..
+        }
+        | AstNode::Lambda {
+            params,
+            defaults,
+            body,
+            ..
+        } => {
+            let fixed = match params.last() {
+                Some((_, Type::Rest(_))) => params.len() - 1,
+                _ => params.len(),
+            };
+            let defaulted = &params[fixed - defaults.len()..fixed];
+            for ((name, _), default) in defaulted.iter().zip(defaults) {
+                let _ = writeln!(out, "{}Default {}", "  ".repeat(depth), name);
+                write_node(out, default, depth + 1);
+            }
+            write_node(out, body, depth);
+        }
+        AstNode::IfExpr {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            write_node(out, condition, depth);
+            write_node(out, then_branch, depth);
+            if let Some(else_branch) = else_branch {
+                write_node(out, else_branch, depth);
+            }
+        }
+        AstNode::Try { body, handler, .. } => {
+            write_node(out, body, depth);
+            write_node(out, handler, depth);
+        }
+        AstNode::Match { scrutinee, arms } => {
+            write_node(out, scrutinee, depth);
+            for arm in arms {
+                let pattern = match &arm.pattern {
+                    Pattern::Literal(literal) => label(literal),
+                    Pattern::Wildcard => "_".to_string(),
+                    Pattern::Variant {
+                        enum_name,
+                        variant,
+                        binding,
+                    } => match binding {
+                        Some(binding) => format!("{}::{}({})", enum_name, variant, binding),
+                        None => format!("{}::{}", enum_name, variant),
+                    },
+                };
+                let _ = writeln!(out, "{}Arm {}", "  ".repeat(depth), pattern);
+                write_node(out, &arm.body, depth + 1);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Operator;
+
+    #[test]
+    fn test_pretty_print_function() {
+        let func = AstNode::FunctionDecl {
+            name: "add".into(),
+            params: vec![("a".into(), Type::I32), ("b".into(), Type::I32)],
+            defaults: vec![],
+            return_type: Some(Type::I32),
+            body: Box::new(AstNode::Block {
+                statements: vec![AstNode::BinaryOp {
+                    left: Box::new(AstNode::Identifier("a".into())),
+                    operator: Operator::Add,
+                    right: Box::new(AstNode::Identifier("b".into())),
+                }],
+                discards_value: false,
+            }),
+            attributes: vec![],
+            is_async: false,
+        };
+
+        assert_eq!(
+            pretty_print(&[func]),
+            "Func add(a: I32, b: I32) -> I32\n  \
+             Block\n    \
+             BinaryOp Add\n      \
+             Identifier a\n      \
+             Identifier b\n"
+        );
+    }
+}
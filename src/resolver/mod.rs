@@ -0,0 +1,386 @@
+#![allow(dead_code)]
+
+use crate::interner::{Symbol, SymbolMap};
+use crate::parser::{AstNode, Param, Pattern, Variant};
+use crate::stdlib::StdLib;
+use std::collections::HashSet;
+
+// Static pass run between parsing and interpretation. It reports calls to
+// functions which are neither declared, bound to a variable, nor builtins, and
+// variables that are used before their `let`. Functions are visible anywhere
+// in the program; variables only after their declaration. Blocks don't open a
+// new scope (matching the interpreter), but function bodies do. It also
+// rejects match arms naming an enum or variant that doesn't exist, and enums
+// declared twice.
+pub struct Resolver {
+    known: HashSet<Symbol>,
+    // Enums declared by earlier programs and by this one
+    enums: SymbolMap<Vec<Variant>>,
+    scopes: Vec<HashSet<Symbol>>,
+    errors: Vec<String>,
+    // Names used that no scope being checked declares, in order of use
+    free: Vec<Symbol>,
+}
+
+impl Resolver {
+    pub fn new<I: IntoIterator<Item = Symbol>>(known: I) -> Self {
+        Resolver {
+            known: known.into_iter().collect(),
+            enums: SymbolMap::default(),
+            scopes: vec![HashSet::new()],
+            errors: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    // Starts with the enums declared by earlier programs
+    pub fn with_enums(mut self, enums: &SymbolMap<Vec<Variant>>) -> Self {
+        self.enums = enums.clone();
+        self
+    }
+
+    // The variables and functions a function's body uses from the scopes
+    // around it, such as the names a closure captures. Each is listed once.
+    pub fn free_variables(params: &[Param], body: &AstNode) -> Vec<Symbol> {
+        let mut resolver = Resolver::new([]);
+        resolver.scopes = vec![params.iter().map(|(name, _)| *name).collect()];
+        resolver.declare(body);
+        resolver.check(body);
+        let mut seen = HashSet::new();
+        resolver.free.retain(|name| seen.insert(*name));
+        resolver.free
+    }
+
+    // Checks every name in `ast`, returning all unresolved names in one error
+    pub fn resolve(mut self, ast: &[AstNode]) -> Result<(), String> {
+        for node in ast {
+            self.declare(node);
+        }
+        for node in ast {
+            self.check(node);
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors.join("\n"))
+        }
+    }
+
+    // Collects every declared function name and enum
+    fn declare(&mut self, node: &AstNode) {
+        match node {
+            AstNode::EnumDecl { name, variants } => {
+                let previous = self.enums.insert(*name, variants.clone());
+                if previous.is_some() {
+                    self.errors
+                        .push(format!("Enum {} is already defined", name));
+                }
+            }
+            AstNode::FunctionDecl { name, body, .. } => {
+                self.known.insert(*name);
+                self.declare(body);
+            }
+            AstNode::Block { statements, .. } => {
+                for stmt in statements {
+                    self.declare(stmt);
+                }
+            }
+            AstNode::IfExpr {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.declare(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.declare(else_branch);
+                }
+            }
+            AstNode::WhileLoop { body, .. } | AstNode::DoWhile { body, .. } => self.declare(body),
+            AstNode::Try { body, handler, .. } => {
+                self.declare(body);
+                self.declare(handler);
+            }
+            AstNode::Match { arms, .. } => {
+                for arm in arms {
+                    self.declare(&arm.body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn is_declared(&mut self, name: Symbol) -> bool {
+        if self.scopes.iter().any(|scope| scope.contains(&name)) {
+            return true;
+        }
+        if !self.known.contains(&name) {
+            self.free.push(name);
+        }
+        self.known.contains(&name)
+    }
+
+    fn define(&mut self, name: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name);
+        }
+    }
+
+    fn check_variant(&mut self, enum_name: Symbol, variant: Symbol) {
+        match self.enums.get(&enum_name) {
+            None => self.errors.push(format!("Undefined enum: {}", enum_name)),
+            Some(variants) if !variants.iter().any(|(name, _)| *name == variant) => self
+                .errors
+                .push(format!("Enum {} has no variant {}", enum_name, variant)),
+            Some(_) => {}
+        }
+    }
+
+    fn check(&mut self, node: &AstNode) {
+        match node {
+            AstNode::Identifier(name) if !self.is_declared(*name) => {
+                self.errors
+                    .push(format!("Variable used before declaration: {}", name));
+            }
+            AstNode::FunctionCall { name, args } => {
+                // A builtin's name still counts as free, as a variable may shadow it
+                if !self.is_declared(*name) && !StdLib::is_builtin(name.as_str()) {
+                    self.errors.push(format!("Undefined function: {}", name));
+                }
+                for arg in args {
+                    self.check(arg);
+                }
+            }
+            AstNode::VariableDecl {
+                name, initializer, ..
+            } => {
+                // Check the initializer first so `let x = x;` needs an earlier x
+                if let Some(initializer) = initializer {
+                    self.check(initializer);
+                }
+                self.define(*name);
+            }
+            AstNode::DestructureDecl {
+                names, initializer, ..
+            } => {
+                self.check(initializer);
+                for name in names {
+                    self.define(*name);
+                }
+            }
+            AstNode::FunctionDecl {
+                params,
+                defaults,
+                body,
+                ..
+            }
+            | AstNode::Lambda {
+                params,
+                defaults,
+                body,
+                ..
+            } => {
+                // Defaults are evaluated in the scope the function is defined in
+                for default in defaults {
+                    self.check(default);
+                }
+                self.scopes
+                    .push(params.iter().map(|(name, _)| *name).collect());
+                self.check(body);
+                self.scopes.pop();
+            }
+            AstNode::Tuple(elements) | AstNode::Interpolation(elements) => {
+                for element in elements {
+                    self.check(element);
+                }
+            }
+            AstNode::TupleAccess { target, .. } => self.check(target),
+            AstNode::IndexAccess { target, index } => {
+                self.check(target);
+                self.check(index);
+            }
+            AstNode::Block { statements, .. } => {
+                for stmt in statements {
+                    self.check(stmt);
+                }
+            }
+            AstNode::IfExpr {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.check(condition);
+                self.check(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check(else_branch);
+                }
+            }
+            AstNode::WhileLoop { condition, body } => {
+                self.check(condition);
+                self.check(body);
+            }
+            AstNode::DoWhile { body, condition } => {
+                self.check(body);
+                self.check(condition);
+            }
+            AstNode::Match { scrutinee, arms } => {
+                self.check(scrutinee);
+                for arm in arms {
+                    if let Pattern::Variant {
+                        enum_name, variant, ..
+                    } = arm.pattern
+                    {
+                        self.check_variant(enum_name, variant);
+                    }
+                    // A bound payload is only in scope in its arm
+                    if let Pattern::Variant {
+                        binding: Some(binding),
+                        ..
+                    } = arm.pattern
+                    {
+                        self.scopes.push([binding].into_iter().collect());
+                        self.check(&arm.body);
+                        self.scopes.pop();
+                    } else {
+                        self.check(&arm.body);
+                    }
+                }
+            }
+            AstNode::Try {
+                body,
+                binding,
+                handler,
+            } => {
+                self.check(body);
+                // The error is only in scope in the handler
+                self.scopes.push([*binding].into_iter().collect());
+                self.check(handler);
+                self.scopes.pop();
+            }
+            AstNode::EnumVariant {
+                payload: Some(payload),
+                ..
+            } => self.check(payload),
+            AstNode::BinaryOp { left, right, .. } => {
+                self.check(left);
+                self.check(right);
+            }
+            AstNode::UnaryOp { operand, .. } => self.check(operand),
+            AstNode::CompoundAssign { target, value, .. } => {
+                self.check(target);
+                self.check(value);
+            }
+            AstNode::Send { channel, value } => {
+                self.check(channel);
+                self.check(value);
+            }
+            AstNode::NamedArgument { value, .. } => self.check(value),
+            AstNode::Receive { channel } => self.check(channel),
+            AstNode::Await { expression } => self.check(expression),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str) -> AstNode {
+        AstNode::FunctionCall {
+            name: name.into(),
+            args: vec![],
+        }
+    }
+
+    #[test]
+    fn test_reports_all_undefined_functions() {
+        let ast = vec![
+            call("println"),
+            AstNode::IfExpr {
+                condition: Box::new(AstNode::Boolean(false)),
+                then_branch: Box::new(AstNode::Block {
+                    statements: vec![call("prnitln")],
+                    discards_value: false,
+                }),
+                else_branch: Some(Box::new(AstNode::Block {
+                    statements: vec![call("missing")],
+                    discards_value: false,
+                })),
+            },
+        ];
+
+        let err = Resolver::new(vec![]).resolve(&ast).unwrap_err();
+        assert!(err.contains("Undefined function: prnitln"));
+        assert!(err.contains("Undefined function: missing"));
+    }
+
+    fn let_decl(name: &str, initializer: AstNode) -> AstNode {
+        AstNode::VariableDecl {
+            name: name.into(),
+            type_annotation: None,
+            initializer: Some(Box::new(initializer)),
+            ownership: None,
+        }
+    }
+
+    #[test]
+    fn test_shadowing_is_allowed() {
+        let ast = vec![
+            let_decl("x", AstNode::Integer(1)),
+            let_decl("x", AstNode::Identifier("x".into())),
+        ];
+        assert!(Resolver::new(vec![]).resolve(&ast).is_ok());
+    }
+
+    #[test]
+    fn test_use_before_declaration() {
+        let ast = vec![
+            let_decl("y", AstNode::Identifier("x".into())),
+            let_decl("x", AstNode::Integer(1)),
+            let_decl("z", AstNode::Identifier("z".into())),
+        ];
+        let err = Resolver::new(vec![]).resolve(&ast).unwrap_err();
+        assert_eq!(
+            err,
+            "Variable used before declaration: x\nVariable used before declaration: z"
+        );
+    }
+
+    #[test]
+    fn test_accepts_known_and_later_declared_functions() {
+        let ast = vec![
+            call("helper"),
+            call("from_repl"),
+            AstNode::FunctionDecl {
+                name: "helper".into(),
+                params: vec![],
+                defaults: vec![],
+                return_type: None,
+                body: Box::new(AstNode::Block {
+                    statements: vec![],
+                    discards_value: false,
+                }),
+                attributes: vec![],
+                is_async: false,
+            },
+        ];
+
+        assert!(Resolver::new(vec!["from_repl".into()])
+            .resolve(&ast)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_free_variables() {
+        let ast = crate::parse(
+            "func f(a: i32) { let b = a + outer; func inner() { b + more } b = inner(); println(b, outer) }",
+        )
+        .unwrap();
+        let AstNode::FunctionDecl { params, body, .. } = &ast[0] else {
+            panic!("expected a function, got {:?}", ast[0]);
+        };
+        let free: Vec<Symbol> = Resolver::free_variables(params, body);
+        assert_eq!(free, vec!["outer".into(), "more".into(), "println".into()]);
+    }
+}
@@ -1,80 +1,212 @@
 use log::info;
 use rand::Rng as _;
 
-use crate::interpreter::Value;
+use crate::interpreter::{Shared, Value};
 use std::{
     collections::HashMap,
-    io::{self, Write},
+    io::{self, BufRead, Write},
+    sync::LazyLock,
 };
 
+// Where builtins read input and write output. Defaults to the process's stdin
+// and stdout; tests and embedders can supply their own reader and writer.
+pub struct Io {
+    // None reads stdin directly, locking it per line so the REPL can share it
+    input: Option<Box<dyn BufRead>>,
+    output: Box<dyn Write>,
+}
+
+impl Io {
+    pub fn new(input: impl BufRead + 'static, output: impl Write + 'static) -> Self {
+        Io {
+            input: Some(Box::new(input)),
+            output: Box::new(output),
+        }
+    }
+
+    // Writes to `output` but still reads the process's stdin
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        Io {
+            input: None,
+            output: Box::new(output),
+        }
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        let mut line = String::new();
+        match &mut self.input {
+            Some(reader) => reader.read_line(&mut line),
+            None => io::stdin().read_line(&mut line),
+        }
+        .map_err(|e| e.to_string())?;
+        Ok(line)
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        self.output
+            .write_all(text.as_bytes())
+            .and_then(|_| self.output.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Default for Io {
+    fn default() -> Self {
+        Io {
+            input: None,
+            output: Box::new(io::stdout()),
+        }
+    }
+}
+
 pub struct StdLib;
 
+// What a builtin needs beyond its arguments and the interpreter's input and
+// output. Sandbox mode refuses every builtin that needs the filesystem.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capability {
+    Basic,
+    Filesystem,
+}
+
+// Built once so name checks don't allocate on every call
+static BUILTINS: LazyLock<HashMap<&'static str, Capability>> =
+    LazyLock::new(|| StdLib::registry().into_iter().collect());
+
 impl StdLib {
     pub fn get_builtin_functions() -> Vec<&'static str> {
+        StdLib::registry()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    // Every builtin, with what it needs access to
+    fn registry() -> Vec<(&'static str, Capability)> {
+        use Capability::{Basic, Filesystem};
         vec![
             // Type conversion functions
-            "to_string",
-            "to_int",
-            "to_float",
-            "to_bool",
+            ("to_string", Basic),
+            ("to_int", Basic),
+            ("parse_int", Basic),
+            ("to_float", Basic),
+            ("to_bool", Basic),
             // IO functions
-            "file_exists",
-            "create_dir",
-            "list_dir",
-            "remove_file",
-            "read_file",
-            "write_file",
-            "input",
-            "raw_input",
-            "println",
-            "print",
+            ("file_exists", Filesystem),
+            ("file_size", Filesystem),
+            ("is_dir", Filesystem),
+            ("is_file", Filesystem),
+            ("file_modified", Filesystem),
+            ("create_dir", Filesystem),
+            ("create_dir_all", Filesystem),
+            ("list_dir", Filesystem),
+            ("walk_dir", Filesystem),
+            ("remove_file", Filesystem),
+            ("copy_file", Filesystem),
+            ("rename_file", Filesystem),
+            ("read_file", Filesystem),
+            ("write_file", Filesystem),
+            ("input", Basic),
+            ("raw_input", Basic),
+            ("prompt", Basic),
+            ("println", Basic),
+            ("print", Basic),
             // String functions
-            "split",
-            "trim",
-            "contains",
-            "replace",
+            ("split", Basic),
+            ("splitn", Basic),
+            ("trim", Basic),
+            ("trim_start", Basic),
+            ("trim_end", Basic),
+            ("strip_prefix", Basic),
+            ("strip_suffix", Basic),
+            ("pad_left", Basic),
+            ("pad_right", Basic),
+            ("contains", Basic),
+            ("replace", Basic),
             // Math functions
-            "abs",
-            "max",
-            "min",
-            "sqrt",
-            "pow",
+            ("abs", Basic),
+            ("max", Basic),
+            ("min", Basic),
+            ("sqrt", Basic),
+            ("pow", Basic),
+            ("fdiv", Basic),
             // Random functions
-            "random",
-            "random_range",
-            "random_choice",
+            ("random", Basic),
+            ("random_range", Basic),
+            ("random_choice", Basic),
             // Collections functions
-            "new_vector",
-            "push",
-            "pop",
-            "set",
-            "new_hashmap",
-            "insert",
-            "get",
+            ("new_vector", Basic),
+            ("push", Basic),
+            ("pop", Basic),
+            ("set", Basic),
+            ("vec_index_of", Basic),
+            ("index_of", Basic),
+            ("new_hashmap", Basic),
+            ("insert", Basic),
+            ("get", Basic),
+            ("get_opt", Basic),
+            // Shared values
+            ("shared", Basic),
+            ("deref", Basic),
+            // Results
+            ("try_read_file", Filesystem),
+            ("try_to_int", Basic),
+            ("try_to_float", Basic),
+            ("try_parse_int", Basic),
+            ("try_get", Basic),
+            ("is_ok", Basic),
+            ("unwrap", Basic),
+            ("unwrap_or", Basic),
+            // Optionals
+            ("is_some", Basic),
+            ("is_none", Basic),
+            // Testing
+            ("assert", Basic),
         ]
     }
 
-    pub fn handle_builtin_function(name: &str, args: Vec<Value>) -> Result<Value, String> {
+    pub fn handle_builtin_function(
+        name: &str,
+        args: Vec<Value>,
+        io: &mut Io,
+    ) -> Result<Value, String> {
         match name {
             // Type conversion functions
             "to_string" => StdLib::to_string(args),
             "to_int" => StdLib::to_int(args),
+            "parse_int" => StdLib::parse_int(args),
             "to_float" => StdLib::to_float(args),
             "to_bool" => StdLib::to_bool(args),
             // Math functions
             "file_exists" => StdLib::file_exists(args),
+            "file_size" => StdLib::file_size(args),
+            "is_dir" => StdLib::is_dir(args),
+            "is_file" => StdLib::is_file(args),
+            "file_modified" => StdLib::file_modified(args),
             "create_dir" => StdLib::create_dir(args),
+            "create_dir_all" => StdLib::create_dir_all(args),
             "list_dir" => StdLib::list_dir(args),
+            "walk_dir" => StdLib::walk_dir(args),
             "remove_file" => StdLib::remove_file(args),
+            "copy_file" => StdLib::copy_file(args),
+            "rename_file" => StdLib::rename_file(args),
             "read_file" => StdLib::read_file(args),
             "write_file" => StdLib::write_file(args),
-            "input" => StdLib::input(),
-            "raw_input" => StdLib::raw_input(),
-            "println" => StdLib::println(args),
-            "print" => StdLib::print(args),
+            "input" => StdLib::input(io),
+            "raw_input" => StdLib::raw_input(io),
+            "prompt" => StdLib::prompt(args, io),
+            "println" => StdLib::println(args, io),
+            "print" => StdLib::print(args, io),
             // String functions
             "split" => StdLib::split(args),
+            "splitn" => StdLib::splitn(args),
             "trim" => StdLib::trim(args),
+            "trim_start" => StdLib::trim_start(args),
+            "trim_end" => StdLib::trim_end(args),
+            "strip_prefix" => StdLib::strip_prefix(args),
+            "strip_suffix" => StdLib::strip_suffix(args),
+            "pad_left" => StdLib::pad(args, "pad_left", true),
+            "pad_right" => StdLib::pad(args, "pad_right", false),
             "contains" => StdLib::contains(args),
             "replace" => StdLib::replace(args),
             // Math functions
@@ -83,6 +215,7 @@ impl StdLib {
             "min" => StdLib::min(args),
             "sqrt" => StdLib::sqrt(args),
             "pow" => StdLib::pow(args),
+            "fdiv" => StdLib::fdiv(args),
             // Random functions
             "random" => Ok(StdLib::random()),
             "random_range" => StdLib::random_range(args),
@@ -92,15 +225,121 @@ impl StdLib {
             "push" => StdLib::vec_push(args),
             "pop" => StdLib::vec_pop(args),
             "set" => StdLib::vec_set(args),
+            // `index_of` is the older name
+            "vec_index_of" | "index_of" => StdLib::vec_index_of(args),
             "new_hashmap" => StdLib::hashmap_new(args),
             "insert" => StdLib::hashmap_insert(args),
             "get" => StdLib::hashmap_get(args),
+            "get_opt" => StdLib::hashmap_get_opt(args),
+            // Shared values
+            "shared" => StdLib::shared(args),
+            "deref" => StdLib::deref(args),
+            // Results: `try_x` runs `x`, returning its error as a value
+            "try_read_file" | "try_to_int" | "try_to_float" | "try_parse_int" | "try_get" => {
+                let result = StdLib::handle_builtin_function(&name["try_".len()..], args, io);
+                Ok(Value::Result(result.map(Box::new)))
+            }
+            "is_ok" => StdLib::is_ok(args),
+            "unwrap" => StdLib::unwrap(args),
+            "unwrap_or" => StdLib::unwrap_or(args),
+            // Optionals
+            "is_some" => StdLib::is_some(args, true),
+            "is_none" => StdLib::is_some(args, false),
+            // Testing
+            "assert" => StdLib::assert(args),
             _ => Err(format!("Unknown built-in function: {}", name)),
         }
     }
 
     pub fn is_builtin(name: &str) -> bool {
-        Self::get_builtin_functions().contains(&name)
+        BUILTINS.contains_key(name)
+    }
+
+    // What the builtin `name` needs access to, if there is one
+    pub fn capability(name: &str) -> Option<Capability> {
+        BUILTINS.get(name).copied()
+    }
+
+    // Where `index` falls in a vector of `len` elements, if it does.
+    // Negative indices count back from the end, as in `v[-1]`.
+    pub(crate) fn vector_index(len: usize, index: i32) -> Option<usize> {
+        let index = if index < 0 {
+            len.checked_sub(index.unsigned_abs() as usize)?
+        } else {
+            index as usize
+        };
+        (index < len).then_some(index)
+    }
+
+    pub fn is_mutating_builtin(name: &str) -> bool {
+        matches!(name, "push" | "pop" | "set" | "insert")
+    }
+
+    // Applies a mutating collection builtin directly to a variable's value.
+    // `args` excludes the target collection itself.
+    pub fn mutate_in_place(
+        name: &str,
+        target: &mut Value,
+        args: Vec<Value>,
+    ) -> Result<Value, String> {
+        match name {
+            "push" => {
+                let [value]: [Value; 1] = args
+                    .try_into()
+                    .map_err(|_| "push expects two arguments: vector and value".to_string())?;
+                match target {
+                    Value::Vector(vec) => {
+                        vec.push(value);
+                        Ok(Value::Vector(vec.clone()))
+                    }
+                    _ => Err("First argument must be a vector".to_string()),
+                }
+            }
+            "pop" => {
+                if !args.is_empty() {
+                    return Err("pop expects one argument: vector".to_string());
+                }
+                match target {
+                    Value::Vector(vec) => vec.pop().ok_or("Vector is empty".to_string()),
+                    _ => Err("Argument must be a vector".to_string()),
+                }
+            }
+            "set" => {
+                let [index, value]: [Value; 2] = args.try_into().map_err(|_| {
+                    "set expects three arguments: vector, index, and value".to_string()
+                })?;
+                let index = match index {
+                    Value::Integer(i) => i,
+                    _ => return Err("Index must be an integer".to_string()),
+                };
+                match target {
+                    Value::Vector(vec) => {
+                        let index = StdLib::vector_index(vec.len(), index)
+                            .ok_or_else(|| "Index out of bounds".to_string())?;
+                        vec[index] = value;
+                        Ok(Value::Vector(vec.clone()))
+                    }
+                    _ => Err("First argument must be a vector".to_string()),
+                }
+            }
+            "insert" => {
+                let [key, value]: [Value; 2] = args.try_into().map_err(|_| {
+                    "insert expects three arguments: hashmap, key, and value".to_string()
+                })?;
+                let key = match key {
+                    Value::String(s) => s,
+                    _ => return Err("Key must be a string".to_string()),
+                };
+                match target {
+                    Value::HashMap(map) => {
+                        map.insert(key, value);
+                        Ok(Value::HashMap(map.clone()))
+                    }
+                    _ => Err("First argument must be a hashmap".to_string()),
+                }
+            }
+            _ => Err(format!("{} is not a mutating built-in function", name)),
+        }
     }
 
     // Type conversion functions
@@ -114,6 +353,14 @@ impl StdLib {
             Value::Float(f) => f.to_string(),
             Value::Boolean(b) => b.to_string(),
             Value::String(s) => s.clone(),
+            // Same form as printing them; unit becomes "()"
+            value @ (Value::Vector(_)
+            | Value::HashMap(_)
+            | Value::Tuple(_)
+            | Value::Unit
+            | Value::EnumVariant { .. }
+            | Value::Result(_)
+            | Value::Optional(_)) => value.to_string(),
             _ => return Err("Cannot convert value to string".to_string()),
         };
 
@@ -126,17 +373,59 @@ impl StdLib {
         }
 
         let result = match &args[0] {
-            Value::String(s) => s
-                .parse::<i32>()
-                .map_err(|_| "Failed to parse string as integer".to_string())?,
+            // Surrounding whitespace is ignored and decimals truncate toward
+            // zero, so user input like " 3.9" still converts
+            Value::String(s) => {
+                let s = s.trim();
+                StdLib::truncate_decimal(s)
+                    .ok_or_else(|| format!("Failed to parse string as integer: {:?}", s))?
+            }
             Value::Float(f) => *f as i32,
             Value::Integer(i) => *i,
+            Value::Boolean(b) => *b as i32,
             _ => return Err("Cannot convert value to integer".to_string()),
         };
 
         Ok(Value::Integer(result))
     }
 
+    // A number literal as the lexer reads it (digits, optionally followed by
+    // `.` and more digits) with an optional sign, truncated toward zero.
+    // Exponents, `inf` and `NaN` aren't literals, so they're refused.
+    fn truncate_decimal(s: &str) -> Option<i32> {
+        let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+        let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if whole.is_empty()
+            || !whole.bytes().all(|b| b.is_ascii_digit())
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let whole = whole.parse::<i64>().ok()?;
+        i32::try_from(if s.starts_with('-') { -whole } else { whole }).ok()
+    }
+
+    pub fn parse_int(args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err("parse_int expects exactly two arguments".to_string());
+        }
+
+        let string = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("parse_int expects a string as the first argument".to_string()),
+        };
+
+        let radix = match &args[1] {
+            Value::Integer(r) if (2..=36).contains(r) => *r as u32,
+            Value::Integer(r) => return Err(format!("Radix must be between 2 and 36, got {}", r)),
+            _ => return Err("parse_int expects an integer radix".to_string()),
+        };
+
+        i32::from_str_radix(string, radix)
+            .map(Value::Integer)
+            .map_err(|_| format!("Failed to parse {:?} as a base-{} integer", string, radix))
+    }
+
     pub fn to_float(args: Vec<Value>) -> Result<Value, String> {
         if args.len() != 1 {
             return Err("to_float expects exactly one argument".to_string());
@@ -148,6 +437,7 @@ impl StdLib {
                 .map_err(|_| "Failed to parse string as float".to_string())?,
             Value::Integer(i) => *i as f64,
             Value::Float(f) => *f,
+            Value::Boolean(b) => *b as i32 as f64,
             _ => return Err("Cannot convert value to float".to_string()),
         };
 
@@ -160,10 +450,14 @@ impl StdLib {
         }
 
         let result = match &args[0] {
-            Value::String(s) => s
-                .parse::<bool>()
-                .map_err(|_| "Failed to parse string as boolean".to_string())?,
+            // Accepts the spellings config files commonly use, in any case
+            Value::String(s) => match s.trim().to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => true,
+                "false" | "0" | "no" | "off" => false,
+                _ => return Err(format!("Failed to parse string as boolean: {:?}", s)),
+            },
             Value::Integer(i) => *i != 0,
+            Value::Float(f) => *f != 0.0,
             Value::Boolean(b) => *b,
             _ => return Err("Cannot convert value to boolean".to_string()),
         };
@@ -185,6 +479,49 @@ impl StdLib {
         Ok(Value::Boolean(std::path::Path::new(filename).exists()))
     }
 
+    // Looks up metadata for a builtin's single path argument. Missing paths
+    // are an error rather than a default value.
+    fn metadata(name: &str, args: &[Value]) -> Result<std::fs::Metadata, String> {
+        if args.len() != 1 {
+            return Err(format!("{} expects exactly one argument", name));
+        }
+
+        let path = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err(format!("{} expects a string argument", name)),
+        };
+
+        std::fs::metadata(path).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn file_size(args: Vec<Value>) -> Result<Value, String> {
+        let size = StdLib::metadata("file_size", &args)?.len();
+        i32::try_from(size)
+            .map(Value::Integer)
+            .map_err(|_| format!("File size {} does not fit in an integer", size))
+    }
+
+    pub fn is_dir(args: Vec<Value>) -> Result<Value, String> {
+        Ok(Value::Boolean(StdLib::metadata("is_dir", &args)?.is_dir()))
+    }
+
+    pub fn is_file(args: Vec<Value>) -> Result<Value, String> {
+        Ok(Value::Boolean(
+            StdLib::metadata("is_file", &args)?.is_file(),
+        ))
+    }
+
+    // Unix milliseconds overflow an i32, so they are returned as a float
+    pub fn file_modified(args: Vec<Value>) -> Result<Value, String> {
+        let modified = StdLib::metadata("file_modified", &args)?
+            .modified()
+            .map_err(|e| e.to_string())?;
+        let since_epoch = modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| e.to_string())?;
+        Ok(Value::Float(since_epoch.as_millis() as f64))
+    }
+
     pub fn create_dir(args: Vec<Value>) -> Result<Value, String> {
         if args.len() != 1 {
             return Err("create_file expects exactly one argument".to_string());
@@ -199,6 +536,20 @@ impl StdLib {
         Ok(Value::Unit)
     }
 
+    pub fn create_dir_all(args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err("create_dir_all expects exactly one argument".to_string());
+        }
+
+        let dirname = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("create_dir_all expects a string argument".to_string()),
+        };
+
+        std::fs::create_dir_all(dirname).map_err(|e| e.to_string())?;
+        Ok(Value::Unit)
+    }
+
     pub fn list_dir(args: Vec<Value>) -> Result<Value, String> {
         if args.len() != 1 {
             return Err("list_dir expects exactly one argument".to_string());
@@ -222,6 +573,64 @@ impl StdLib {
         Ok(Value::Vector(result))
     }
 
+    pub fn walk_dir(args: Vec<Value>) -> Result<Value, String> {
+        if args.is_empty() || args.len() > 2 {
+            return Err("walk_dir expects one or two arguments".to_string());
+        }
+
+        let dirname = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("walk_dir expects a string as the first argument".to_string()),
+        };
+
+        let include_dirs = match args.get(1) {
+            None => false,
+            Some(Value::Boolean(b)) => *b,
+            Some(_) => return Err("walk_dir expects a boolean as the second argument".to_string()),
+        };
+
+        let root = std::path::Path::new(dirname);
+        let mut paths = Vec::new();
+        StdLib::walk(root, root, include_dirs, &mut paths)?;
+        paths.sort();
+
+        Ok(Value::Vector(
+            paths.into_iter().map(Value::String).collect(),
+        ))
+    }
+
+    // Collects paths under `dir` relative to `root`. Symlinks are listed as
+    // entries but never followed, so links to directories can't revisit or
+    // loop back into the tree.
+    fn walk(
+        root: &std::path::Path,
+        dir: &std::path::Path,
+        include_dirs: bool,
+        paths: &mut Vec<String>,
+    ) -> Result<(), String> {
+        for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let is_dir = entry.file_type().map_err(|e| e.to_string())?.is_dir();
+            let path = entry.path();
+            let relative = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+
+            if is_dir {
+                if include_dirs {
+                    paths.push(relative);
+                }
+                StdLib::walk(root, &path, include_dirs, paths)?;
+            } else {
+                paths.push(relative);
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn remove_file(args: Vec<Value>) -> Result<Value, String> {
         if args.len() != 1 {
             return Err("remove_file expects exactly one argument".to_string());
@@ -236,6 +645,27 @@ impl StdLib {
         Ok(Value::Unit)
     }
 
+    // Extracts the source and destination paths of copy_file and rename_file
+    fn path_pair<'a>(name: &str, args: &'a [Value]) -> Result<(&'a str, &'a str), String> {
+        match args {
+            [Value::String(src), Value::String(dst)] => Ok((src, dst)),
+            [_, _] => Err(format!("{} expects two string arguments", name)),
+            _ => Err(format!("{} expects exactly two arguments", name)),
+        }
+    }
+
+    pub fn copy_file(args: Vec<Value>) -> Result<Value, String> {
+        let (src, dst) = StdLib::path_pair("copy_file", &args)?;
+        std::fs::copy(src, dst).map_err(|e| e.to_string())?;
+        Ok(Value::Unit)
+    }
+
+    pub fn rename_file(args: Vec<Value>) -> Result<Value, String> {
+        let (src, dst) = StdLib::path_pair("rename_file", &args)?;
+        std::fs::rename(src, dst).map_err(|e| e.to_string())?;
+        Ok(Value::Unit)
+    }
+
     pub fn read_file(args: Vec<Value>) -> Result<Value, String> {
         if args.len() != 1 {
             return Err("read_file expects exactly one argument".to_string());
@@ -273,58 +703,86 @@ impl StdLib {
         Ok(Value::Unit)
     }
 
-    pub fn input() -> Result<Value, String> {
-        let mut input = String::new();
-        io::stdin()
-            .read_line(&mut input)
-            .map_err(|e| e.to_string())?;
-        Ok(Value::String(input.trim().to_string()))
+    pub fn input(io: &mut Io) -> Result<Value, String> {
+        Ok(Value::String(io.read_line()?.trim().to_string()))
     }
 
-    pub fn raw_input() -> Result<Value, String> {
-        let mut input = String::new();
-        io::stdin()
-            .read_line(&mut input)
-            .map_err(|e| e.to_string())?;
-        Ok(Value::String(input))
+    pub fn raw_input(io: &mut Io) -> Result<Value, String> {
+        Ok(Value::String(io.read_line()?))
     }
 
-    pub fn print(args: Vec<Value>) -> Result<Value, String> {
+    // Writes the prompt without a newline, then reads one trimmed line
+    pub fn prompt(args: Vec<Value>, io: &mut Io) -> Result<Value, String> {
         if args.len() != 1 {
-            return Err("print expects exactly one argument".to_string());
+            return Err("prompt expects exactly one argument".to_string());
         }
 
-        let output = match &args[0] {
-            Value::String(s) => s.clone(),
-            Value::Integer(i) => i.to_string(),
-            Value::Float(f) => f.to_string(),
-            Value::Boolean(b) => b.to_string(),
-            _ => return Err("Unsupported type for print".to_string()),
-        };
-
-        print!("{}", output);
-        io::stdout().flush().map_err(|e| e.to_string())?;
-        Ok(Value::Unit)
+        io.write(&StdLib::format_printable(&args[0], "prompt")?)?;
+        StdLib::input(io)
     }
 
-    pub fn println(args: Vec<Value>) -> Result<Value, String> {
-        if args.len() != 1 {
-            return Err("println expects exactly one argument".to_string());
+    fn format_printable(value: &Value, name: &str) -> Result<String, String> {
+        match value {
+            Value::Unit
+            | Value::Function { .. }
+            | Value::Future(_)
+            | Value::Channel(_)
+            | Value::Actor(_) => Err(format!("Unsupported type for {}", name)),
+            Value::Shared(shared) => StdLib::format_printable(&shared.get(), name),
+            _ => Ok(value.to_string()),
         }
+    }
 
-        let output = match &args[0] {
-            Value::String(s) => s.clone(),
-            Value::Integer(i) => i.to_string(),
-            Value::Float(f) => f.to_string(),
-            Value::Boolean(b) => b.to_string(),
-            _ => return Err("Unsupported type for println".to_string()),
-        };
+    pub fn print(args: Vec<Value>, io: &mut Io) -> Result<Value, String> {
+        let output = args
+            .iter()
+            .map(|arg| StdLib::format_printable(arg, "print"))
+            .collect::<Result<Vec<_>, _>>()?
+            .concat();
 
-        println!("{}", output);
+        io.write(&output)?;
+        Ok(Value::Unit)
+    }
+
+    pub fn println(args: Vec<Value>, io: &mut Io) -> Result<Value, String> {
+        let output = args
+            .iter()
+            .map(|arg| StdLib::format_printable(arg, "println"))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(" ");
+
+        io.write(&(output + "\n"))?;
         Ok(Value::Unit)
     }
 
     // String functions
+
+    // Splits into at most `limit` parts, the last keeping any remaining
+    // delimiters. An empty delimiter splits into individual characters.
+    fn split_parts(string: &str, delimiter: &str, limit: Option<usize>) -> Vec<Value> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let parts: Vec<String> = if delimiter.is_empty() {
+            let mut chars = string.chars();
+            let mut parts: Vec<String> = chars
+                .by_ref()
+                .take(limit.saturating_sub(1))
+                .map(String::from)
+                .collect();
+            let rest: String = chars.collect();
+            if !rest.is_empty() {
+                parts.push(rest);
+            }
+            parts
+        } else {
+            string
+                .splitn(limit, delimiter)
+                .map(str::to_string)
+                .collect()
+        };
+
+        parts.into_iter().map(Value::String).collect()
+    }
+
     pub fn split(args: Vec<Value>) -> Result<Value, String> {
         if args.len() != 2 {
             return Err("split expects exactly two arguments".to_string());
@@ -340,12 +798,34 @@ impl StdLib {
             _ => return Err("split expects a string as the second argument".to_string()),
         };
 
-        let parts: Vec<Value> = string
-            .split(delimiter)
-            .map(|s| Value::String(s.to_string()))
-            .collect();
+        Ok(Value::Vector(StdLib::split_parts(string, delimiter, None)))
+    }
+
+    pub fn splitn(args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 3 {
+            return Err("splitn expects exactly three arguments".to_string());
+        }
+
+        let string = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("splitn expects a string as the first argument".to_string()),
+        };
 
-        Ok(Value::Vector(parts))
+        let delimiter = match &args[1] {
+            Value::String(s) => s,
+            _ => return Err("splitn expects a string as the second argument".to_string()),
+        };
+
+        let limit = match &args[2] {
+            Value::Integer(n) if *n > 0 => *n as usize,
+            _ => return Err("splitn expects a positive integer as the third argument".to_string()),
+        };
+
+        Ok(Value::Vector(StdLib::split_parts(
+            string,
+            delimiter,
+            Some(limit),
+        )))
     }
 
     pub fn trim(args: Vec<Value>) -> Result<Value, String> {
@@ -361,6 +841,109 @@ impl StdLib {
         Ok(Value::String(string.trim().to_string()))
     }
 
+    pub fn trim_start(args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err("trim_start expects exactly one argument".to_string());
+        }
+
+        let string = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("trim_start expects a string as the first argument".to_string()),
+        };
+
+        Ok(Value::String(string.trim_start().to_string()))
+    }
+
+    pub fn trim_end(args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 1 {
+            return Err("trim_end expects exactly one argument".to_string());
+        }
+
+        let string = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err("trim_end expects a string as the first argument".to_string()),
+        };
+
+        Ok(Value::String(string.trim_end().to_string()))
+    }
+
+    // Removes `affix` with `strip`, returning the string unchanged if it's absent
+    fn strip_affix(
+        name: &str,
+        args: Vec<Value>,
+        strip: for<'a> fn(&'a str, &str) -> Option<&'a str>,
+    ) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err(format!("{} expects exactly two arguments", name));
+        }
+
+        let string = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err(format!("{} expects a string as the first argument", name)),
+        };
+
+        let affix = match &args[1] {
+            Value::String(s) => s,
+            _ => return Err(format!("{} expects a string as the second argument", name)),
+        };
+
+        Ok(Value::String(
+            strip(string, affix).unwrap_or(string).to_string(),
+        ))
+    }
+
+    pub fn strip_prefix(args: Vec<Value>) -> Result<Value, String> {
+        StdLib::strip_affix("strip_prefix", args, |s, prefix| s.strip_prefix(prefix))
+    }
+
+    pub fn strip_suffix(args: Vec<Value>) -> Result<Value, String> {
+        StdLib::strip_affix("strip_suffix", args, |s, suffix| s.strip_suffix(suffix))
+    }
+
+    // Pads a string to `width` characters with an optional single-character
+    // fill (a space by default). Longer strings are returned unchanged.
+    pub fn pad(args: Vec<Value>, name: &str, left: bool) -> Result<Value, String> {
+        if args.len() != 2 && args.len() != 3 {
+            return Err(format!("{} expects two or three arguments", name));
+        }
+
+        let string = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err(format!("{} expects a string as the first argument", name)),
+        };
+
+        let width = match &args[1] {
+            Value::Integer(w) if *w >= 0 => *w as usize,
+            _ => {
+                return Err(format!(
+                    "{} expects a non-negative integer width as the second argument",
+                    name
+                ))
+            }
+        };
+
+        let fill = match args.get(2) {
+            None => ' ',
+            Some(Value::String(s)) if s.chars().count() == 1 => s.chars().next().unwrap(),
+            Some(_) => {
+                return Err(format!(
+                    "{} expects a single-character string as the fill",
+                    name
+                ))
+            }
+        };
+
+        let padding = fill
+            .to_string()
+            .repeat(width.saturating_sub(string.chars().count()));
+
+        Ok(Value::String(if left {
+            padding + string
+        } else {
+            string.clone() + &padding
+        }))
+    }
+
     pub fn contains(args: Vec<Value>) -> Result<Value, String> {
         if args.len() != 2 {
             return Err("contains expects exactly two arguments".to_string());
@@ -368,7 +951,10 @@ impl StdLib {
 
         let string = match &args[0] {
             Value::String(s) => s,
-            _ => return Err("contains expects a string as the first argument".to_string()),
+            Value::Vector(vec) => return Ok(Value::Boolean(vec.contains(&args[1]))),
+            _ => {
+                return Err("contains expects a string or vector as the first argument".to_string())
+            }
         };
 
         let substring = match &args[1] {
@@ -408,53 +994,77 @@ impl StdLib {
             return Err("abs expects exactly one argument".to_string());
         }
 
-        let result = match &args[0] {
-            Value::Integer(i) => i.abs().into(),
-            Value::Float(f) => f.abs(),
-            _ => return Err("Cannot take absolute value of non-numeric value".to_string()),
-        };
-
-        Ok(Value::Float(result))
-    }
-
-    pub fn max(args: Vec<Value>) -> Result<Value, String> {
-        if args.len() != 2 {
-            return Err("max expects exactly two arguments".to_string());
+        match &args[0] {
+            Value::Integer(i) => i
+                .checked_abs()
+                .map(Value::Integer)
+                .ok_or("abs overflowed integer range".to_string()),
+            Value::Float(f) => Ok(Value::Float(f.abs())),
+            _ => Err("Cannot take absolute value of non-numeric value".to_string()),
         }
+    }
 
-        let a = match &args[0] {
-            Value::Integer(i) => *i as f64,
-            Value::Float(f) => *f,
-            _ => return Err("max expects numeric arguments".to_string()),
-        };
-
-        let b = match &args[1] {
-            Value::Integer(i) => *i as f64,
-            Value::Float(f) => *f,
-            _ => return Err("max expects numeric arguments".to_string()),
+    // Picks the preferred of two numbers, staying integral when both inputs are
+    fn pick_numeric(
+        name: &str,
+        a: &Value,
+        b: &Value,
+        prefer_left: fn(f64, f64) -> bool,
+    ) -> Result<Value, String> {
+        let as_float = |value: &Value| match value {
+            Value::Integer(i) => Ok(*i as f64),
+            Value::Float(f) => Ok(*f),
+            _ => Err(format!("{} expects numeric arguments", name)),
         };
 
-        Ok(Value::Float(a.max(b)))
+        let (x, y) = (as_float(a)?, as_float(b)?);
+        let left = prefer_left(x, y);
+        match (a, b) {
+            (Value::Integer(_), Value::Integer(_)) => Ok(if left { a.clone() } else { b.clone() }),
+            _ => Ok(Value::Float(if left { x } else { y })),
+        }
     }
 
-    pub fn min(args: Vec<Value>) -> Result<Value, String> {
-        if args.len() != 2 {
-            return Err("min expects exactly two arguments".to_string());
+    fn fold_numeric(
+        name: &str,
+        args: Vec<Value>,
+        prefer_left: fn(f64, f64) -> bool,
+    ) -> Result<Value, String> {
+        match args.as_slice() {
+            [a, b] => StdLib::pick_numeric(name, a, b, prefer_left),
+            [Value::Vector(items)] => {
+                let first = items
+                    .first()
+                    .ok_or(format!("{} of an empty vector", name))?;
+                if !matches!(first, Value::Integer(_) | Value::Float(_)) {
+                    return Err(format!("{} expects numeric arguments", name));
+                }
+                let integers = matches!(first, Value::Integer(_));
+                let mut best = first.clone();
+                for item in &items[1..] {
+                    if matches!(item, Value::Integer(_)) != integers {
+                        return Err(format!(
+                            "{} expects a vector of a single numeric type",
+                            name
+                        ));
+                    }
+                    best = StdLib::pick_numeric(name, &best, item, prefer_left)?;
+                }
+                Ok(best)
+            }
+            _ => Err(format!(
+                "{} expects two numeric arguments or a single vector",
+                name
+            )),
         }
+    }
 
-        let a = match &args[0] {
-            Value::Integer(i) => *i as f64,
-            Value::Float(f) => *f,
-            _ => return Err("min expects numeric arguments".to_string()),
-        };
-
-        let b = match &args[1] {
-            Value::Integer(i) => *i as f64,
-            Value::Float(f) => *f,
-            _ => return Err("min expects numeric arguments".to_string()),
-        };
+    pub fn max(args: Vec<Value>) -> Result<Value, String> {
+        StdLib::fold_numeric("max", args, |a, b| a >= b)
+    }
 
-        Ok(Value::Float(a.min(b)))
+    pub fn min(args: Vec<Value>) -> Result<Value, String> {
+        StdLib::fold_numeric("min", args, |a, b| a <= b)
     }
 
     pub fn sqrt(args: Vec<Value>) -> Result<Value, String> {
@@ -491,6 +1101,30 @@ impl StdLib {
         Ok(Value::Float(base.powf(exponent)))
     }
 
+    pub fn fdiv(args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err("fdiv expects exactly two arguments".to_string());
+        }
+
+        let dividend = match &args[0] {
+            Value::Integer(i) => *i as f64,
+            Value::Float(f) => *f,
+            _ => return Err("fdiv expects numeric arguments".to_string()),
+        };
+
+        let divisor = match &args[1] {
+            Value::Integer(i) => *i as f64,
+            Value::Float(f) => *f,
+            _ => return Err("fdiv expects numeric arguments".to_string()),
+        };
+
+        if divisor == 0.0 {
+            return Err("Division by zero".to_string());
+        }
+
+        Ok(Value::Float(dividend / divisor))
+    }
+
     // Random functions
     pub fn random() -> Value {
         Value::Float(rand::random::<f64>())
@@ -544,7 +1178,8 @@ impl StdLib {
             Value::Vector(vec) => {
                 let mut new_vec = vec.clone();
                 let index = match &args[1] {
-                    Value::Integer(i) => *i as usize,
+                    Value::Integer(i) => StdLib::vector_index(vec.len(), *i)
+                        .ok_or_else(|| "Index out of bounds".to_string())?,
                     _ => return Err("Index must be an integer".to_string()),
                 };
                 new_vec[index] = args[2].clone();
@@ -583,6 +1218,20 @@ impl StdLib {
         }
     }
 
+    pub fn vec_index_of(args: Vec<Value>) -> Result<Value, String> {
+        if args.len() != 2 {
+            return Err("vec_index_of expects two arguments: vector and value".to_string());
+        }
+
+        match &args[0] {
+            Value::Vector(vec) => {
+                let index = vec.iter().position(|item| item == &args[1]);
+                Ok(Value::Integer(index.map_or(-1, |i| i as i32)))
+            }
+            _ => Err("First argument must be a vector".to_string()),
+        }
+    }
+
     pub fn hashmap_new(_args: Vec<Value>) -> Result<Value, String> {
         Ok(Value::HashMap(HashMap::new()))
     }
@@ -624,4 +1273,685 @@ impl StdLib {
             _ => Err("First argument must be a hashmap".to_string()),
         }
     }
+
+    // Like `get`, but a missing key gives `none` instead of an error
+    pub fn hashmap_get_opt(args: Vec<Value>) -> Result<Value, String> {
+        match args.as_slice() {
+            [Value::HashMap(map), Value::String(key)] => {
+                Ok(Value::Optional(map.get(key).cloned().map(Box::new)))
+            }
+            [Value::HashMap(_), _] => Err("Key must be a string".to_string()),
+            [_, _] => Err("First argument must be a hashmap".to_string()),
+            _ => Err("get_opt expects two arguments: hashmap and key".to_string()),
+        }
+    }
+
+    // Wraps a value so every variable it's stored in shares it
+    pub fn shared(args: Vec<Value>) -> Result<Value, String> {
+        let [value]: [Value; 1] = args
+            .try_into()
+            .map_err(|_| "shared expects exactly one argument".to_string())?;
+        Ok(Value::Shared(Shared::new(value)))
+    }
+
+    // A copy of a shared value's contents. Variables already read as their
+    // contents, so anything that isn't shared is returned unchanged.
+    pub fn deref(args: Vec<Value>) -> Result<Value, String> {
+        let [value]: [Value; 1] = args
+            .try_into()
+            .map_err(|_| "deref expects exactly one argument".to_string())?;
+        match value {
+            Value::Shared(shared) => Ok(shared.get()),
+            Value::Weak(weak) => weak
+                .get()
+                .ok_or_else(|| "Weak reference dangling".to_string()),
+            value => Ok(value),
+        }
+    }
+
+    pub fn is_ok(args: Vec<Value>) -> Result<Value, String> {
+        match args.as_slice() {
+            [Value::Result(result)] => Ok(Value::Boolean(result.is_ok())),
+            [_] => Err("is_ok expects a result".to_string()),
+            _ => Err("is_ok expects exactly one argument".to_string()),
+        }
+    }
+
+    // The value of an Ok result or some optional; an Err result fails with
+    // its error, and none fails too
+    pub fn unwrap(args: Vec<Value>) -> Result<Value, String> {
+        let [value]: [Value; 1] = args
+            .try_into()
+            .map_err(|_| "unwrap expects exactly one argument".to_string())?;
+        match value {
+            Value::Result(result) => result.map(|value| *value),
+            Value::Optional(optional) => optional
+                .map(|value| *value)
+                .ok_or_else(|| "Called unwrap on none".to_string()),
+            _ => Err("unwrap expects a result or optional".to_string()),
+        }
+    }
+
+    // The value of an Ok result or some optional, or `default` otherwise
+    pub fn unwrap_or(args: Vec<Value>) -> Result<Value, String> {
+        let [value, default]: [Value; 2] = args
+            .try_into()
+            .map_err(|_| "unwrap_or expects two arguments: value and default".to_string())?;
+        match value {
+            Value::Result(result) => Ok(result.map_or(default, |value| *value)),
+            Value::Optional(optional) => Ok(optional.map_or(default, |value| *value)),
+            _ => Err("unwrap_or expects a result or optional".to_string()),
+        }
+    }
+
+    // `is_some` when `some` is true, `is_none` otherwise
+    pub fn is_some(args: Vec<Value>, some: bool) -> Result<Value, String> {
+        let name = if some { "is_some" } else { "is_none" };
+        match args.as_slice() {
+            [Value::Optional(optional)] => Ok(Value::Boolean(optional.is_some() == some)),
+            [_] => Err(format!("{} expects an optional", name)),
+            _ => Err(format!("{} expects exactly one argument", name)),
+        }
+    }
+
+    // Fails with `message`, or a generic one, unless `condition` is true
+    pub fn assert(args: Vec<Value>) -> Result<Value, String> {
+        match args.as_slice() {
+            [Value::Boolean(true)] | [Value::Boolean(true), _] => Ok(Value::Unit),
+            [Value::Boolean(false)] => Err("Assertion failed".to_string()),
+            [Value::Boolean(false), message] => Err(format!("Assertion failed: {}", message)),
+            [_] | [_, _] => Err("assert expects a boolean condition".to_string()),
+            _ => Err("assert expects a condition and an optional message".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An Io whose output can be inspected after it's been handed over
+    #[derive(Clone, Default)]
+    struct Captured(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    fn captured_io(input: &'static str) -> (Io, Captured) {
+        let output = Captured::default();
+        (Io::new(io::Cursor::new(input), output.clone()), output)
+    }
+
+    #[test]
+    fn test_is_builtin() {
+        for name in StdLib::get_builtin_functions() {
+            assert!(StdLib::is_builtin(name), "{}", name);
+        }
+        assert!(!StdLib::is_builtin("prnitln"));
+        assert!(!StdLib::is_builtin(""));
+        assert!(!StdLib::is_builtin("PRINTLN"));
+    }
+
+    #[test]
+    fn test_capability() {
+        for name in ["read_file", "write_file", "walk_dir", "remove_file"] {
+            assert_eq!(StdLib::capability(name), Some(Capability::Filesystem));
+        }
+        assert_eq!(StdLib::capability("println"), Some(Capability::Basic));
+        assert_eq!(StdLib::capability("to_int"), Some(Capability::Basic));
+        assert_eq!(StdLib::capability("prnitln"), None);
+    }
+
+    #[test]
+    fn test_println_multiple_arguments() {
+        let (mut io, output) = captured_io("");
+        let args = vec![
+            Value::String("x".to_string()),
+            Value::Integer(1),
+            Value::Boolean(true),
+        ];
+        assert_eq!(StdLib::println(args, &mut io), Ok(Value::Unit));
+        assert_eq!(StdLib::println(vec![], &mut io), Ok(Value::Unit));
+        assert_eq!(output.text(), "x 1 true\n\n");
+    }
+
+    #[test]
+    fn test_println_collections() {
+        let (mut io, output) = captured_io("");
+        let nested = Value::Vector(vec![
+            Value::Integer(1),
+            Value::Vector(vec![Value::Integer(2), Value::Integer(3)]),
+        ]);
+        let mut map = HashMap::new();
+        map.insert("k".to_string(), Value::String("v".to_string()));
+
+        assert_eq!(StdLib::println(vec![nested], &mut io), Ok(Value::Unit));
+        assert_eq!(
+            StdLib::println(vec![Value::HashMap(map)], &mut io),
+            Ok(Value::Unit)
+        );
+        assert_eq!(output.text(), "[1, [2, 3]]\n{\"k\": \"v\"}\n");
+    }
+
+    #[test]
+    fn test_to_string_collections() {
+        let nested = Value::Vector(vec![
+            Value::Integer(1),
+            Value::Vector(vec![Value::String("a".to_string()), Value::Unit]),
+        ]);
+        let mut map = HashMap::new();
+        map.insert("b".to_string(), Value::Vector(vec![Value::Integer(2)]));
+        map.insert("a".to_string(), Value::Boolean(true));
+
+        let string = |s: &str| Ok(Value::String(s.to_string()));
+        assert_eq!(StdLib::to_string(vec![nested]), string("[1, [\"a\", ()]]"));
+        assert_eq!(
+            StdLib::to_string(vec![Value::HashMap(map)]),
+            string("{\"a\": true, \"b\": [2]}")
+        );
+        assert_eq!(StdLib::to_string(vec![Value::Unit]), string("()"));
+    }
+
+    #[test]
+    fn test_to_bool_spellings() {
+        let to_bool = |s: &str| StdLib::to_bool(vec![Value::String(s.to_string())]);
+
+        for s in ["true", "TRUE", "True", "1", "yes", "YES", "on", " On "] {
+            assert_eq!(to_bool(s), Ok(Value::Boolean(true)), "{}", s);
+        }
+        for s in ["false", "FALSE", "0", "no", "No", "off", "OFF"] {
+            assert_eq!(to_bool(s), Ok(Value::Boolean(false)), "{}", s);
+        }
+        assert_eq!(
+            to_bool("maybe"),
+            Err("Failed to parse string as boolean: \"maybe\"".to_string())
+        );
+        assert_eq!(
+            StdLib::to_bool(vec![Value::Float(0.5)]),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            StdLib::to_bool(vec![Value::Float(0.0)]),
+            Ok(Value::Boolean(false))
+        );
+    }
+
+    #[test]
+    fn test_to_int_forgiving_strings() {
+        let to_int = |s: &str| StdLib::to_int(vec![Value::String(s.to_string())]);
+
+        assert_eq!(to_int("3.9"), Ok(Value::Integer(3)));
+        assert_eq!(to_int("-3.9"), Ok(Value::Integer(-3)));
+        assert_eq!(to_int(" 5 "), Ok(Value::Integer(5)));
+        assert_eq!(to_int("+7"), Ok(Value::Integer(7)));
+        assert_eq!(
+            to_int("abc"),
+            Err("Failed to parse string as integer: \"abc\"".to_string())
+        );
+        assert!(to_int("NaN").is_err());
+        assert!(to_int("1e12").is_err());
+        assert!(to_int("1e2").is_err());
+        assert!(to_int(".5").is_err());
+        assert!(to_int("1.2.3").is_err());
+        assert!(to_int("--1").is_err());
+        assert_eq!(to_int("5."), Ok(Value::Integer(5)));
+        assert_eq!(to_int("-0.5"), Ok(Value::Integer(0)));
+        assert_eq!(to_int("-2147483648.5"), Ok(Value::Integer(i32::MIN)));
+        assert_eq!(to_int("2147483647.9"), Ok(Value::Integer(i32::MAX)));
+        assert!(to_int("2147483648.0").is_err());
+        assert!(to_int("-2147483649").is_err());
+    }
+
+    #[test]
+    fn test_booleans_convert_to_numbers() {
+        assert_eq!(
+            StdLib::to_int(vec![Value::Boolean(true)]),
+            Ok(Value::Integer(1))
+        );
+        assert_eq!(
+            StdLib::to_int(vec![Value::Boolean(false)]),
+            Ok(Value::Integer(0))
+        );
+        assert_eq!(
+            StdLib::to_float(vec![Value::Boolean(true)]),
+            Ok(Value::Float(1.0))
+        );
+        assert_eq!(
+            StdLib::to_float(vec![Value::Boolean(false)]),
+            Ok(Value::Float(0.0))
+        );
+    }
+
+    #[test]
+    fn test_vector_contains_and_vec_index_of() {
+        let ints = Value::Vector(vec![Value::Integer(1), Value::Integer(2)]);
+        let strings = Value::Vector(vec![
+            Value::String("a".to_string()),
+            Value::String("b".to_string()),
+        ]);
+
+        assert_eq!(
+            StdLib::contains(vec![ints.clone(), Value::Integer(2)]),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            StdLib::contains(vec![strings.clone(), Value::String("c".to_string())]),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(
+            StdLib::vec_index_of(vec![strings, Value::String("b".to_string())]),
+            Ok(Value::Integer(1))
+        );
+        assert_eq!(
+            StdLib::vec_index_of(vec![ints.clone(), Value::Integer(5)]),
+            Ok(Value::Integer(-1))
+        );
+
+        let mut io = Io::default();
+        for name in ["vec_index_of", "index_of"] {
+            assert_eq!(
+                StdLib::handle_builtin_function(
+                    name,
+                    vec![ints.clone(), Value::Integer(2)],
+                    &mut io
+                ),
+                Ok(Value::Integer(1))
+            );
+        }
+    }
+
+    #[test]
+    fn test_abs_preserves_type() {
+        assert_eq!(StdLib::abs(vec![Value::Integer(-3)]), Ok(Value::Integer(3)));
+        assert_eq!(StdLib::abs(vec![Value::Float(-3.5)]), Ok(Value::Float(3.5)));
+        assert!(StdLib::abs(vec![Value::Integer(i32::MIN)]).is_err());
+    }
+
+    #[test]
+    fn test_set_counts_negative_indices_from_the_end() {
+        let ints =
+            |items: &[i32]| Value::Vector(items.iter().map(|&i| Value::Integer(i)).collect());
+        let mut target = ints(&[1, 2, 3]);
+        assert_eq!(
+            StdLib::mutate_in_place(
+                "set",
+                &mut target,
+                vec![Value::Integer(-1), Value::Integer(9)]
+            ),
+            Ok(ints(&[1, 2, 9]))
+        );
+        assert_eq!(
+            StdLib::vec_set(vec![
+                ints(&[1, 2, 3]),
+                Value::Integer(-3),
+                Value::Integer(0)
+            ]),
+            Ok(ints(&[0, 2, 3]))
+        );
+        for index in [-4, 3] {
+            assert_eq!(
+                StdLib::mutate_in_place(
+                    "set",
+                    &mut target,
+                    vec![Value::Integer(index), Value::Unit]
+                ),
+                Err("Index out of bounds".to_string())
+            );
+            assert_eq!(
+                StdLib::vec_set(vec![ints(&[1, 2, 3]), Value::Integer(index), Value::Unit]),
+                Err("Index out of bounds".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_min() {
+        assert_eq!(
+            StdLib::max(vec![Value::Integer(2), Value::Integer(7)]),
+            Ok(Value::Integer(7))
+        );
+        assert_eq!(
+            StdLib::min(vec![Value::Float(2.5), Value::Float(1.5)]),
+            Ok(Value::Float(1.5))
+        );
+        let vec = Value::Vector(vec![
+            Value::Integer(4),
+            Value::Integer(9),
+            Value::Integer(1),
+        ]);
+        assert_eq!(StdLib::max(vec![vec.clone()]), Ok(Value::Integer(9)));
+        assert_eq!(StdLib::min(vec![vec]), Ok(Value::Integer(1)));
+        assert!(StdLib::max(vec![Value::Vector(vec![])]).is_err());
+        let mixed = Value::Vector(vec![Value::Integer(1), Value::Float(2.0)]);
+        assert!(StdLib::max(vec![mixed]).is_err());
+        assert_eq!(
+            StdLib::max(vec![Value::Vector(vec![Value::Float(0.5)])]),
+            Ok(Value::Float(0.5))
+        );
+        assert_eq!(
+            StdLib::max(vec![strings(&["a"])]),
+            Err("max expects numeric arguments".to_string())
+        );
+        assert_eq!(
+            StdLib::min(vec![strings(&["a", "b"])]),
+            Err("min expects numeric arguments".to_string())
+        );
+    }
+
+    #[test]
+    fn test_print_rejects_unsupported_types() {
+        let (mut io, _) = captured_io("");
+        assert!(StdLib::print(vec![Value::Integer(1), Value::Unit], &mut io).is_err());
+    }
+
+    fn strings(items: &[&str]) -> Value {
+        Value::Vector(items.iter().map(|s| Value::String(s.to_string())).collect())
+    }
+
+    #[test]
+    fn test_splitn() {
+        let split = |s: &str, d: &str, n: i32| {
+            StdLib::splitn(vec![
+                Value::String(s.to_string()),
+                Value::String(d.to_string()),
+                Value::Integer(n),
+            ])
+        };
+
+        assert_eq!(split("a:b:c", ":", 2), Ok(strings(&["a", "b:c"])));
+        assert_eq!(split("a:b:c", ":", 5), Ok(strings(&["a", "b", "c"])));
+        assert_eq!(split("abc", "", 2), Ok(strings(&["a", "bc"])));
+        assert!(split("a:b", ":", 0).is_err());
+        assert_eq!(
+            StdLib::split(vec![
+                Value::String("abc".to_string()),
+                Value::String("".to_string())
+            ]),
+            Ok(strings(&["a", "b", "c"]))
+        );
+    }
+
+    #[test]
+    fn test_trim_and_strip() {
+        let string = |s: &str| Value::String(s.to_string());
+
+        assert_eq!(
+            StdLib::trim_start(vec![string("  a b  ")]),
+            Ok(string("a b  "))
+        );
+        assert_eq!(
+            StdLib::trim_end(vec![string("  a b  ")]),
+            Ok(string("  a b"))
+        );
+        assert_eq!(
+            StdLib::strip_prefix(vec![string("key=value"), string("key=")]),
+            Ok(string("value"))
+        );
+        assert_eq!(
+            StdLib::strip_prefix(vec![string("value"), string("key=")]),
+            Ok(string("value"))
+        );
+        assert_eq!(
+            StdLib::strip_suffix(vec![string("main.aki"), string(".aki")]),
+            Ok(string("main"))
+        );
+        assert_eq!(
+            StdLib::strip_suffix(vec![string("main.rs"), string(".aki")]),
+            Ok(string("main.rs"))
+        );
+    }
+
+    #[test]
+    fn test_pad() {
+        let string = |s: &str| Value::String(s.to_string());
+
+        assert_eq!(
+            StdLib::pad(vec![string("ab"), Value::Integer(4)], "pad_left", true),
+            Ok(string("  ab"))
+        );
+        assert_eq!(
+            StdLib::pad(
+                vec![string("7"), Value::Integer(3), string("0")],
+                "pad_left",
+                true
+            ),
+            Ok(string("007"))
+        );
+        assert_eq!(
+            StdLib::pad(
+                vec![string("ab"), Value::Integer(4), string(".")],
+                "pad_right",
+                false
+            ),
+            Ok(string("ab.."))
+        );
+        assert_eq!(
+            StdLib::pad(
+                vec![string("toolong"), Value::Integer(3)],
+                "pad_right",
+                false
+            ),
+            Ok(string("toolong"))
+        );
+        assert!(StdLib::pad(
+            vec![string("ab"), Value::Integer(4), string("--")],
+            "pad_left",
+            true
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_int_radix() {
+        let parse = |s: &str, radix: i32| {
+            StdLib::parse_int(vec![Value::String(s.to_string()), Value::Integer(radix)])
+        };
+
+        assert_eq!(parse("ff", 16), Ok(Value::Integer(255)));
+        assert_eq!(parse("101", 2), Ok(Value::Integer(5)));
+        assert!(parse("102", 2).is_err());
+        assert!(parse("10", 1).is_err());
+        assert!(parse("10", 37).is_err());
+    }
+
+    #[test]
+    fn test_file_metadata() {
+        let dir = std::env::temp_dir().join(format!("aki_metadata_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("data.txt");
+        std::fs::write(&file, "hello").unwrap();
+        let path = |p: &std::path::Path| vec![Value::String(p.to_string_lossy().to_string())];
+
+        assert_eq!(StdLib::file_size(path(&file)), Ok(Value::Integer(5)));
+        assert_eq!(StdLib::is_file(path(&file)), Ok(Value::Boolean(true)));
+        assert_eq!(StdLib::is_dir(path(&file)), Ok(Value::Boolean(false)));
+        assert_eq!(StdLib::is_dir(path(&dir)), Ok(Value::Boolean(true)));
+        assert!(matches!(
+            StdLib::file_modified(path(&file)),
+            Ok(Value::Float(millis)) if millis > 0.0
+        ));
+
+        let missing = dir.join("missing.txt");
+        assert!(StdLib::file_size(path(&missing)).is_err());
+        assert!(StdLib::is_dir(path(&missing)).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_walk_dir() {
+        let root = std::env::temp_dir().join(format!("aki_walk_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        std::fs::write(root.join("top.txt"), "").unwrap();
+        std::fs::write(root.join("a/b/deep.txt"), "").unwrap();
+        // Links to directories are listed, not followed
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&root, root.join("a/loop")).unwrap();
+            std::os::unix::fs::symlink(root.join("a/b"), root.join("b_link")).unwrap();
+        }
+        let dir = Value::String(root.to_string_lossy().to_string());
+
+        let files = StdLib::walk_dir(vec![dir.clone()]);
+        let with_dirs = StdLib::walk_dir(vec![dir, Value::Boolean(true)]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+        if cfg!(unix) {
+            assert_eq!(
+                files,
+                Ok(strings(&["a/b/deep.txt", "a/loop", "b_link", "top.txt"]))
+            );
+            assert_eq!(
+                with_dirs,
+                Ok(strings(&[
+                    "a",
+                    "a/b",
+                    "a/b/deep.txt",
+                    "a/loop",
+                    "b_link",
+                    "top.txt"
+                ]))
+            );
+        } else {
+            assert_eq!(files, Ok(strings(&["a/b/deep.txt", "top.txt"])));
+            assert_eq!(
+                with_dirs,
+                Ok(strings(&["a", "a/b", "a/b/deep.txt", "top.txt"]))
+            );
+        }
+    }
+
+    #[test]
+    fn test_copy_rename_and_create_dir_all() {
+        let root = std::env::temp_dir().join(format!("aki_copy_{}", std::process::id()));
+        let path = |p: &str| Value::String(root.join(p).to_string_lossy().to_string());
+
+        assert_eq!(StdLib::create_dir_all(vec![path("x/y/z")]), Ok(Value::Unit));
+        assert!(root.join("x/y/z").is_dir());
+
+        std::fs::write(root.join("a.txt"), "data").unwrap();
+        assert_eq!(
+            StdLib::copy_file(vec![path("a.txt"), path("x/b.txt")]),
+            Ok(Value::Unit)
+        );
+        assert_eq!(
+            StdLib::rename_file(vec![path("a.txt"), path("x/y/c.txt")]),
+            Ok(Value::Unit)
+        );
+        let copied = std::fs::read_to_string(root.join("x/b.txt")).unwrap();
+        let renamed = std::fs::read_to_string(root.join("x/y/c.txt")).unwrap();
+        let original_exists = root.join("a.txt").exists();
+        let missing = StdLib::copy_file(vec![path("missing.txt"), path("d.txt")]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(copied, "data");
+        assert_eq!(renamed, "data");
+        assert!(!original_exists);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_prompt_writes_message_and_reads_line() {
+        let (mut io, output) = captured_io("  Ada  \nignored\n");
+
+        let result = StdLib::prompt(vec![Value::String("Name: ".to_string())], &mut io);
+
+        assert_eq!(result, Ok(Value::String("Ada".to_string())));
+        assert_eq!(output.text(), "Name: ");
+    }
+
+    #[test]
+    fn test_assert() {
+        let mut io = Io::default();
+        let mut call = |args: Vec<Value>| StdLib::handle_builtin_function("assert", args, &mut io);
+        assert_eq!(call(vec![Value::Boolean(true)]), Ok(Value::Unit));
+        assert_eq!(
+            call(vec![Value::Boolean(false)]),
+            Err("Assertion failed".to_string())
+        );
+        assert_eq!(
+            call(vec![
+                Value::Boolean(false),
+                Value::String("sum".to_string())
+            ]),
+            Err("Assertion failed: sum".to_string())
+        );
+        assert!(call(vec![Value::Integer(1)]).is_err());
+        assert!(call(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_try_builtins_return_results() {
+        let mut io = Io::default();
+        let mut call = |name: &str, args: Vec<Value>| {
+            StdLib::handle_builtin_function(name, args, &mut io).unwrap()
+        };
+        let missing = call(
+            "try_read_file",
+            vec![Value::String("/no/such/file.txt".to_string())],
+        );
+        assert!(matches!(&missing, Value::Result(Err(_))));
+        assert_eq!(call("is_ok", vec![missing.clone()]), Value::Boolean(false));
+        assert_eq!(
+            call(
+                "unwrap_or",
+                vec![missing.clone(), Value::String("default".to_string())]
+            ),
+            Value::String("default".to_string())
+        );
+
+        let parsed = call("try_to_int", vec![Value::String("42".to_string())]);
+        assert_eq!(parsed, Value::Result(Ok(Box::new(Value::Integer(42)))));
+        assert_eq!(call("is_ok", vec![parsed.clone()]), Value::Boolean(true));
+        assert_eq!(call("unwrap", vec![parsed.clone()]), Value::Integer(42));
+        assert_eq!(
+            call("unwrap_or", vec![parsed, Value::Integer(0)]),
+            Value::Integer(42)
+        );
+        assert!(StdLib::unwrap(vec![missing]).is_err());
+    }
+
+    #[test]
+    fn test_optional_lookups() {
+        let mut map = HashMap::new();
+        map.insert("port".to_string(), Value::Integer(8080));
+        let map = Value::HashMap(map);
+        let key = |key: &str| Value::String(key.to_string());
+
+        let present = StdLib::hashmap_get_opt(vec![map.clone(), key("port")]).unwrap();
+        let absent = StdLib::hashmap_get_opt(vec![map, key("host")]).unwrap();
+        assert_eq!(present.to_string(), "some(8080)");
+        assert_eq!(absent, Value::Optional(None));
+
+        assert_eq!(
+            StdLib::is_some(vec![present.clone()], true),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            StdLib::is_some(vec![absent.clone()], false),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            StdLib::unwrap_or(vec![present, Value::Integer(80)]),
+            Ok(Value::Integer(8080))
+        );
+        assert_eq!(
+            StdLib::unwrap_or(vec![absent.clone(), Value::Integer(80)]),
+            Ok(Value::Integer(80))
+        );
+        assert!(StdLib::unwrap(vec![absent]).is_err());
+    }
 }
@@ -0,0 +1,355 @@
+use crate::interner::Symbol;
+use crate::interpreter::{Interpreter, Value};
+use crate::parser::{AstNode, Operator, UnaryOperator};
+
+// Flat instructions for a small stack machine. Every compiled expression
+// leaves exactly one value on the stack; jump targets are instruction indices.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Push(Value),
+    Pop,
+    Load(Symbol),
+    // A variable being passed on, which moves the value out of `~` bindings
+    Move(Symbol),
+    // Unannotated `let`: defines the name in the current scope with the type
+    // inferred from the value, keeping the value
+    Define(Symbol),
+    // `=` and compound assignment: updates the nearest binding, keeping the value
+    Assign(Symbol),
+    Binary(Operator),
+    Unary(UnaryOperator),
+    // Peeks the left operand of `&&` or `||` and jumps past the right one if
+    // it already decides the result
+    ShortCircuit(Operator, usize),
+    Jump(usize),
+    // Pops a condition, jumping if it is false and erroring if not a boolean
+    JumpIfFalse(usize),
+    // Anything the compiler doesn't lower runs through the tree-walker
+    Eval(AstNode),
+}
+
+// Lowers `node` into instructions that leave its value on the stack
+pub fn compile(node: &AstNode) -> Vec<Instruction> {
+    let mut compiler = Compiler { code: Vec::new() };
+    compiler.expression(node);
+    compiler.code
+}
+
+struct Compiler {
+    code: Vec<Instruction>,
+}
+
+impl Compiler {
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.code.push(instruction);
+        self.code.len() - 1
+    }
+
+    // Points the jump at `at` to the next instruction to be emitted
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len();
+        match &mut self.code[at] {
+            Instruction::Jump(to)
+            | Instruction::JumpIfFalse(to)
+            | Instruction::ShortCircuit(_, to) => *to = target,
+            other => unreachable!("patching a non-jump instruction {:?}", other),
+        }
+    }
+
+    fn expression(&mut self, node: &AstNode) {
+        match node {
+            AstNode::Integer(n) => {
+                self.emit(Instruction::Push(Value::Integer(*n)));
+            }
+            AstNode::Float(f) => {
+                self.emit(Instruction::Push(Value::Float(*f)));
+            }
+            AstNode::String(s) => {
+                self.emit(Instruction::Push(Value::String(s.clone())));
+            }
+            AstNode::Boolean(b) => {
+                self.emit(Instruction::Push(Value::Boolean(*b)));
+            }
+
+            AstNode::Identifier(name) => {
+                self.emit(Instruction::Load(*name));
+            }
+
+            AstNode::VariableDecl {
+                name,
+                initializer,
+                type_annotation: None,
+                ownership: None,
+            } => {
+                match initializer {
+                    Some(init) => self.moving(init),
+                    None => {
+                        self.emit(Instruction::Push(Value::Unit));
+                    }
+                }
+                self.emit(Instruction::Define(*name));
+            }
+
+            AstNode::BinaryOp {
+                left,
+                operator: operator @ (Operator::And | Operator::Or),
+                right,
+            } => {
+                self.expression(left);
+                let short = self.emit(Instruction::ShortCircuit(operator.clone(), 0));
+                self.expression(right);
+                self.emit(Instruction::Binary(operator.clone()));
+                self.patch(short);
+            }
+
+            AstNode::BinaryOp {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left);
+                self.expression(right);
+                self.emit(Instruction::Binary(operator.clone()));
+            }
+
+            AstNode::UnaryOp {
+                operator: operator @ (UnaryOperator::Inc | UnaryOperator::Dec),
+                operand,
+            } => match operand.as_ref() {
+                AstNode::Identifier(name) => {
+                    let base = match operator {
+                        UnaryOperator::Inc => Operator::Add,
+                        _ => Operator::Sub,
+                    };
+                    self.update(*name, base, None);
+                }
+                _ => self.fallback(node),
+            },
+
+            AstNode::UnaryOp { operator, operand } => {
+                self.expression(operand);
+                self.emit(Instruction::Unary(operator.clone()));
+            }
+
+            AstNode::CompoundAssign {
+                operator,
+                target,
+                value,
+            } => {
+                let AstNode::Identifier(name) = target.as_ref() else {
+                    return self.fallback(node);
+                };
+                let base = match operator {
+                    Operator::Assign => {
+                        self.moving(value);
+                        self.emit(Instruction::Assign(*name));
+                        return;
+                    }
+                    Operator::Inc => return self.update(*name, Operator::Add, None),
+                    Operator::Dec => return self.update(*name, Operator::Sub, None),
+                    Operator::SelfAdd => Operator::Add,
+                    Operator::SelfSub => Operator::Sub,
+                    Operator::SelfMul => Operator::Mul,
+                    Operator::SelfDiv => Operator::Div,
+                    Operator::SelfMod => Operator::Mod,
+                    _ => return self.fallback(node),
+                };
+                self.update(*name, base, Some(value));
+            }
+
+            AstNode::Block {
+                statements,
+                discards_value,
+            } => {
+                for (i, stmt) in statements.iter().enumerate() {
+                    if i > 0 {
+                        self.emit(Instruction::Pop);
+                    }
+                    self.expression(stmt);
+                }
+                if *discards_value && !statements.is_empty() {
+                    self.emit(Instruction::Pop);
+                }
+                if *discards_value || statements.is_empty() {
+                    self.emit(Instruction::Push(Value::Unit));
+                }
+            }
+
+            AstNode::IfExpr {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                let to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.expression(then_branch);
+                let to_end = self.emit(Instruction::Jump(0));
+                self.patch(to_else);
+                match else_branch {
+                    Some(else_branch) => self.expression(else_branch),
+                    None => {
+                        self.emit(Instruction::Push(Value::Unit));
+                    }
+                }
+                self.patch(to_end);
+            }
+
+            AstNode::WhileLoop { condition, body } => {
+                let start = self.code.len();
+                self.expression(condition);
+                let exit = self.emit(Instruction::JumpIfFalse(0));
+                self.expression(body);
+                self.emit(Instruction::Pop);
+                self.emit(Instruction::Jump(start));
+                self.patch(exit);
+                self.emit(Instruction::Push(Value::Unit));
+            }
+
+            _ => self.fallback(node),
+        }
+    }
+
+    fn moving(&mut self, node: &AstNode) {
+        match node {
+            AstNode::Identifier(name) => {
+                self.emit(Instruction::Move(*name));
+            }
+            _ => self.expression(node),
+        }
+    }
+
+    // `name op= value`, or `name op= 1` for increments. The current value is
+    // read before `value` runs, as in the tree-walker.
+    fn update(&mut self, name: Symbol, operator: Operator, value: Option<&AstNode>) {
+        self.emit(Instruction::Load(name));
+        match value {
+            Some(value) => self.expression(value),
+            None => {
+                self.emit(Instruction::Push(Value::Integer(1)));
+            }
+        }
+        self.emit(Instruction::Binary(operator));
+        self.emit(Instruction::Assign(name));
+    }
+
+    fn fallback(&mut self, node: &AstNode) {
+        self.emit(Instruction::Eval(node.clone()));
+    }
+}
+
+// Runs `code` against the interpreter's current scope and returns the value
+// left on top of the stack
+pub fn run(interpreter: &mut Interpreter, code: &[Instruction]) -> Result<Value, String> {
+    let mut stack: Vec<Value> = Vec::new();
+    let mut pc = 0;
+
+    while let Some(instruction) = code.get(pc) {
+        pc += 1;
+        match instruction {
+            Instruction::Push(value) => stack.push(value.clone()),
+            Instruction::Pop => {
+                stack.pop();
+            }
+            Instruction::Load(name) => stack.push(interpreter.lookup(*name)?),
+            Instruction::Move(name) => stack.push(interpreter.take_variable(*name)?),
+            Instruction::Define(name) => {
+                let value = top(&stack)?.clone();
+                interpreter.declare(*name, value, None, false)?;
+            }
+            Instruction::Assign(name) => {
+                let value = top(&stack)?.clone();
+                interpreter.assign_variable(*name, value)?;
+            }
+            Instruction::Binary(operator) => {
+                let right = pop(&mut stack)?;
+                let left = pop(&mut stack)?;
+                stack.push(interpreter.evaluate_binary_op(operator.clone(), left, right)?);
+            }
+            Instruction::Unary(operator) => {
+                let operand = pop(&mut stack)?;
+                stack.push(interpreter.evaluate_unary_op(operator.clone(), operand)?);
+            }
+            Instruction::ShortCircuit(operator, target) => match (operator, top(&stack)?) {
+                (Operator::And, Value::Boolean(false)) | (Operator::Or, Value::Boolean(true)) => {
+                    pc = *target
+                }
+                (_, Value::Boolean(_)) => {}
+                _ => return Err("Invalid operator for types".to_string()),
+            },
+            Instruction::Jump(target) => {
+                // Jumping back is the next iteration of a loop
+                if *target < pc {
+                    interpreter.take_step()?;
+                }
+                pc = *target
+            }
+            Instruction::JumpIfFalse(target) => match pop(&mut stack)? {
+                Value::Boolean(true) => {}
+                Value::Boolean(false) => pc = *target,
+                other => return Err(Interpreter::condition_error(&other)),
+            },
+            Instruction::Eval(node) => stack.push(interpreter.interpret(node)?),
+        }
+    }
+
+    pop(&mut stack)
+}
+
+fn top(stack: &[Value]) -> Result<&Value, String> {
+    stack.last().ok_or_else(|| "VM stack underflow".to_string())
+}
+
+fn pop(stack: &mut Vec<Value>) -> Result<Value, String> {
+    stack.pop().ok_or_else(|| "VM stack underflow".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn compile_source(source: &str) -> Vec<Instruction> {
+        compile(&parse(source).unwrap()[0])
+    }
+
+    #[test]
+    fn test_compiles_counting_loop() {
+        let code = compile_source("while i < 3 { i += 1 }");
+        assert_eq!(
+            code,
+            vec![
+                Instruction::Load("i".into()),
+                Instruction::Push(Value::Integer(3)),
+                Instruction::Binary(Operator::Lt),
+                Instruction::JumpIfFalse(10),
+                Instruction::Load("i".into()),
+                Instruction::Push(Value::Integer(1)),
+                Instruction::Binary(Operator::Add),
+                Instruction::Assign("i".into()),
+                Instruction::Pop,
+                Instruction::Jump(0),
+                Instruction::Push(Value::Unit),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_nodes_fall_back_to_the_tree_walker() {
+        let code = compile_source("while f(1) { }");
+        assert!(matches!(
+            code[0],
+            Instruction::Eval(AstNode::FunctionCall { .. })
+        ));
+    }
+
+    #[test]
+    fn test_runs_against_interpreter_scope() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define("total", Value::Integer(0));
+        let code = compile_source(
+            "while total < 10 { if total % 2 == 0 || false { total += 3 } else { total += 1 } }",
+        );
+        assert_eq!(run(&mut interpreter, &code), Ok(Value::Unit));
+        assert_eq!(interpreter.eval("total"), Ok(Value::Integer(11)));
+    }
+}
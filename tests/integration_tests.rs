@@ -1 +1,1098 @@
+use animikiikode::interpreter::{CallEvent, Shared};
+use animikiikode::{Interpreter, Value};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
 
+#[test]
+fn test_eval_returns_last_value() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        interpreter.eval("let x = 20; x * 2 + 2"),
+        Ok(Value::Integer(42))
+    );
+    assert_eq!(interpreter.eval("x"), Ok(Value::Integer(20)));
+    assert_eq!(interpreter.eval(""), Ok(Value::Unit));
+}
+
+#[test]
+fn test_eval_reports_errors() {
+    let mut interpreter = Interpreter::new();
+    assert!(interpreter.eval("let = ;").is_err());
+    assert_eq!(
+        interpreter.eval("missing()"),
+        Err("Undefined function: missing".to_string())
+    );
+}
+
+#[test]
+fn test_reset_forgets_definitions() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("let x = 1; func f() { 2 } enum E { A }")
+        .unwrap();
+    interpreter.reset();
+    assert!(interpreter.defined_names().is_empty());
+    assert_eq!(
+        interpreter.eval("x"),
+        Err("Variable used before declaration: x".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("f()"),
+        Err("Undefined function: f".to_string())
+    );
+    assert_eq!(interpreter.eval("to_int(\"7\")"), Ok(Value::Integer(7)));
+}
+
+#[test]
+fn test_define_injects_host_values() {
+    let mut interpreter = Interpreter::new();
+    interpreter.define("greeting", Value::String("hello".to_string()));
+    assert_eq!(
+        interpreter.eval(r#"replace(greeting, "h", "j")"#),
+        Ok(Value::String("jello".to_string()))
+    );
+}
+
+#[test]
+fn test_call_script_and_builtin_functions() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("func add(a: i32, b: i32) -> i32 { a + b }")
+        .unwrap();
+
+    assert_eq!(
+        interpreter.call("add", vec![Value::Integer(2), Value::Integer(3)]),
+        Ok(Value::Integer(5))
+    );
+    assert_eq!(
+        interpreter.call("abs", vec![Value::Integer(-4)]),
+        Ok(Value::Integer(4))
+    );
+    assert!(interpreter.call("add", vec![Value::Integer(1)]).is_err());
+    assert!(interpreter.call("nope", vec![]).is_err());
+}
+
+#[test]
+fn test_user_function_shadows_builtin() {
+    let mut interpreter = Interpreter::new();
+    let code = r#"
+        func max(a: i32, b: i32) -> i32 { a + b }
+        max(2, 3)
+    "#;
+    assert_eq!(interpreter.eval(code), Ok(Value::Integer(5)));
+    assert_eq!(interpreter.eval("let min = 1; min"), Ok(Value::Integer(1)));
+}
+
+#[test]
+fn test_bytecode_loops_match_tree_walker() {
+    let programs = [
+        r#"
+            func step(n: i32) -> i32 { n + 2 }
+            let i = 0;
+            let evens = 0;
+            while i < 20 {
+                if i % 2 == 0 && i != 4 { evens++ }
+                i = step(i) - 1;
+            }
+            evens
+        "#,
+        "let n = 3; while n { n -= 1 }",
+        "let d = 5; while d > -1 { d /= d - 1 }",
+    ];
+    for program in programs {
+        let mut walked = Interpreter::new();
+        let mut compiled = Interpreter::new();
+        compiled.set_bytecode(true);
+        assert_eq!(compiled.eval(program), walked.eval(program), "{}", program);
+    }
+    assert_eq!(Interpreter::new().eval(programs[0]), Ok(Value::Integer(9)));
+}
+
+#[test]
+fn test_await_async_function() {
+    let mut interpreter = Interpreter::new();
+    let code = r#"
+        let runs = 0;
+        async func add(a: i32, b: i32) -> i32 {
+            runs += 1;
+            a + b
+        }
+        let pending = add(2, 3);
+    "#;
+    interpreter.eval(code).unwrap();
+
+    // The body only runs once the future is awaited, and only once
+    assert_eq!(interpreter.eval("runs"), Ok(Value::Integer(0)));
+    assert_eq!(interpreter.eval("await pending"), Ok(Value::Integer(5)));
+    assert_eq!(interpreter.eval("await pending"), Ok(Value::Integer(5)));
+    assert_eq!(interpreter.eval("runs"), Ok(Value::Integer(1)));
+
+    assert_eq!(
+        interpreter.eval("await add(1, 1) * 2"),
+        Ok(Value::Integer(4))
+    );
+    assert_eq!(interpreter.eval("await 7"), Ok(Value::Integer(7)));
+    assert!(interpreter.eval("add(1)").is_err());
+}
+
+#[test]
+fn test_run_main_awaits_async_main() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("async func helper() -> i32 { 20 } async func main() { await helper() + 1 }")
+        .unwrap();
+    assert_eq!(interpreter.run_main(), Ok(Value::Integer(21)));
+}
+
+#[test]
+fn test_actor_accumulates_messages() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.eval(
+        "
+        func #actor counter(total: i32, replies: dyn, amount: i32) {
+            total += amount;
+            send(replies, total);
+        }
+        let replies = channel();
+        let worker = counter(0, replies);
+        send(worker, 2);
+        send(worker, 5);
+        let first = recv(replies);
+        (first, recv(replies))
+        ",
+    );
+    assert_eq!(
+        result,
+        Ok(Value::Tuple(vec![Value::Integer(2), Value::Integer(7)]))
+    );
+    assert!(interpreter
+        .eval("recv(replies)")
+        .unwrap_err()
+        .contains("empty channel"));
+}
+
+#[test]
+fn test_sync_function_rejects_shared_values() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        interpreter.eval("let limit = 3; func #sync scale(x: i32) { x * limit } scale(2)"),
+        Ok(Value::Integer(6))
+    );
+
+    interpreter.define(
+        "slot",
+        Value::Tuple(vec![Value::Shared(Shared::new(Value::Integer(0)))]),
+    );
+    assert_eq!(
+        interpreter.eval("func #sync read() { slot }"),
+        Err("#sync function read captures slot, which references the local heap".to_string())
+    );
+
+    // Only names the body uses count, and parameters and locals shadow
+    // outer names
+    assert_eq!(
+        interpreter.eval(
+            "func #sync add(slot: i32) { let ch = slot; ch + limit } \
+             func #sync twice(x: i32) { add(add(x)) } twice(1)"
+        ),
+        Ok(Value::Integer(7))
+    );
+
+    // Channels, actors, futures and ordinary functions hold heap references
+    interpreter
+        .eval("let ch = channel(); func helper() { 1 } async func later() { 2 } let f = later();")
+        .unwrap();
+    for (body, name) in [
+        ("send(ch, 1)", "ch"),
+        ("helper()", "helper"),
+        ("await f", "f"),
+    ] {
+        assert_eq!(
+            interpreter.eval(&format!("func #sync use_it() {{ {} }}", body)),
+            Err(format!(
+                "#sync function use_it captures {}, which references the local heap",
+                name
+            ))
+        );
+    }
+}
+
+#[test]
+fn test_unique_value_moves_into_function() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.eval(
+        "
+        func consume(v: ~i32) -> i32 { v * 2 }
+        let plain = 4;
+        let owned: ~i32 = 21;
+        consume(plain) + consume(owned)
+        ",
+    );
+    assert_eq!(result, Ok(Value::Integer(50)));
+    assert_eq!(interpreter.eval("plain"), Ok(Value::Integer(4)));
+    assert_eq!(
+        interpreter.eval("owned + 1"),
+        Err("Use of moved value: owned".to_string())
+    );
+
+    // Assigning a new value makes the binding usable again
+    assert_eq!(
+        interpreter.eval("owned = 3; let other = owned; owned"),
+        Err("Use of moved value: owned".to_string())
+    );
+    assert_eq!(interpreter.eval("other"), Ok(Value::Integer(3)));
+}
+
+#[test]
+fn test_weak_reference_dangles_after_owner_goes_away() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.eval(
+        "
+        let owner: @i32 = 42;
+        let #weak watcher = owner;
+        let seen = watcher;
+        let owner = 0;
+        seen
+        ",
+    );
+    assert_eq!(result, Ok(Value::Integer(42)));
+    assert_eq!(
+        interpreter.eval("watcher + 1"),
+        Err("Weak reference dangling: watcher".to_string())
+    );
+
+    // Another strong owner keeps the value alive
+    let result = interpreter.eval(
+        "
+        let first: @i32 = 1;
+        let second: @i32 = first;
+        let #weak observer = first;
+        first = 7;
+        let first = 0;
+        observer
+        ",
+    );
+    assert_eq!(result, Ok(Value::Integer(7)));
+}
+
+#[test]
+fn test_inferred_types_reject_wrong_assignments() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        interpreter.eval("let count = 5; count = \"no\";"),
+        Err("Type mismatch: count has type i32, found string".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("let name = \"aki\"; name = 3;"),
+        Err("Type mismatch: name has type string, found integer".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("count += 2; name = \"kode\"; (count, name)"),
+        Ok(Value::Tuple(vec![
+            Value::Integer(7),
+            Value::String("kode".to_string())
+        ]))
+    );
+    assert_eq!(
+        interpreter.eval("let flag: bool = 1;"),
+        Err("Type mismatch: flag has type bool, found integer".to_string())
+    );
+}
+
+#[test]
+fn test_sized_integers_check_their_range() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        interpreter.eval("let byte: u8 = 255; let small: i8 = -128; (byte, small)"),
+        Ok(Value::Tuple(vec![
+            Value::Integer(255),
+            Value::Integer(-128)
+        ]))
+    );
+    assert_eq!(
+        interpreter.eval("let b: u8 = 300;"),
+        Err("Integer 300 out of range for b of type u8".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("let x: u8 = -1;"),
+        Err("Integer -1 out of range for x of type u8".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("small -= 1;"),
+        Err("Integer -129 out of range for small of type i8".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("let count: u32 = 0; count -= 1;"),
+        Err("Integer -1 out of range for count of type u32".to_string())
+    );
+}
+
+#[test]
+fn test_shared_vector_grows_in_place() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.eval(
+        "
+        let items: @Vec<i32> = Vec::new();
+        let alias: @Vec<i32> = items;
+        let copy = items;
+        push(items, 1);
+        push(alias, 2);
+        (items, alias, copy)
+        ",
+    );
+    let grown = Value::Vector(vec![Value::Integer(1), Value::Integer(2)]);
+    assert_eq!(
+        result,
+        Ok(Value::Tuple(vec![
+            grown.clone(),
+            grown,
+            Value::Vector(vec![])
+        ]))
+    );
+}
+
+#[test]
+fn test_shared_builtin_aliases_a_vector() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.eval(
+        "
+        let first = shared(Vec::new());
+        let second: @Vec<i32> = first;
+        push(first, 1);
+        push(second, 2);
+        (deref(first), second, deref(shared(3)))
+        ",
+    );
+    let grown = Value::Vector(vec![Value::Integer(1), Value::Integer(2)]);
+    assert_eq!(
+        result,
+        Ok(Value::Tuple(vec![grown.clone(), grown, Value::Integer(3)]))
+    );
+}
+
+#[test]
+fn test_assigning_shared_value_updates_every_holder() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.eval(
+        "
+        let total: @i32 = 0;
+        let view: @i32 = total;
+        func add(n: i32) { total += n }
+        add(2);
+        add(3);
+        view
+        ",
+    );
+    assert_eq!(result, Ok(Value::Integer(5)));
+}
+
+#[test]
+fn test_trailing_semicolon_discards_block_value() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        interpreter.eval("func f() { 5 } f()"),
+        Ok(Value::Integer(5))
+    );
+    assert_eq!(interpreter.eval("func g() { 5; } g()"), Ok(Value::Unit));
+    assert_eq!(
+        interpreter.eval("let x = 1; func h() { if x > 0 { x } else { 0 }; } h()"),
+        Ok(Value::Unit)
+    );
+}
+
+#[test]
+fn test_empty_blocks_evaluate_to_unit() {
+    let programs = [
+        "func nothing() { } nothing()",
+        "if true { } else { 1 }",
+        "if false { 1 } else { }",
+        "let i = 0; while i < 3 { i += 1; if i > 10 { } }",
+    ];
+    for program in programs {
+        let mut walked = Interpreter::new();
+        let mut compiled = Interpreter::new();
+        compiled.set_bytecode(true);
+        assert_eq!(walked.eval(program), Ok(Value::Unit), "{}", program);
+        assert_eq!(compiled.eval(program), Ok(Value::Unit), "{}", program);
+    }
+
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("func main() { }").unwrap();
+    assert_eq!(interpreter.run_main(), Ok(Value::Unit));
+}
+
+#[test]
+fn test_nested_function_is_local_to_its_scope() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval(
+            "
+            func outer(n: i32) {
+                let offset = 1;
+                func scale(x: i32) { x * 2 + offset }
+                offset = 10;
+                scale(n)
+            }
+            ",
+        )
+        .unwrap();
+    assert_eq!(interpreter.eval("outer(4)"), Ok(Value::Integer(18)));
+    assert_eq!(
+        interpreter.eval("scale(4)"),
+        Err("Undefined function: scale".to_string())
+    );
+}
+
+#[test]
+fn test_default_parameter_values() {
+    let greeting =
+        |parts: [&str; 3]| Value::Tuple(parts.map(|part| Value::String(part.to_string())).to_vec());
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval(
+            r#"
+            let punctuation = "!";
+            func greet(name: string, greeting: string = "Hello", end: string = punctuation) {
+                (greeting, name, end)
+            }
+            "#,
+        )
+        .unwrap();
+
+    assert_eq!(
+        interpreter.eval(r#"greet("Ada")"#),
+        Ok(greeting(["Hello", "Ada", "!"]))
+    );
+    assert_eq!(
+        interpreter.eval(r#"greet("Ada", "Hi", ".")"#),
+        Ok(greeting(["Hi", "Ada", "."]))
+    );
+    // Defaults are evaluated on each call, in the function's closure
+    assert_eq!(
+        interpreter.eval(r#"punctuation = "?"; greet("Ada", "Hey")"#),
+        Ok(greeting(["Hey", "Ada", "?"]))
+    );
+    assert_eq!(
+        interpreter.eval("greet()"),
+        Err("Function expected at least 1 arguments but got 0".to_string())
+    );
+    assert!(interpreter
+        .eval("func bad(a: i32 = 1, b: i32) { a + b }")
+        .is_err());
+}
+
+#[test]
+fn test_named_arguments() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("func span(start: i32, end: i32, step: i32 = 1) { (start, end, step) }")
+        .unwrap();
+    let span = |start, end, step| {
+        Ok(Value::Tuple(vec![
+            Value::Integer(start),
+            Value::Integer(end),
+            Value::Integer(step),
+        ]))
+    };
+
+    assert_eq!(interpreter.eval("span(end: 9, start: 2)"), span(2, 9, 1));
+    assert_eq!(interpreter.eval("span(0, step: 3, end: 6)"), span(0, 6, 3));
+    assert_eq!(
+        interpreter.eval("span(0, start: 1, end: 2)"),
+        Err("Argument start given more than once".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("span(0, stop: 2)"),
+        Err("No parameter named stop".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("span(step: 2, start: 0)"),
+        Err("Missing argument for end".to_string())
+    );
+    assert!(interpreter.eval("span(end: 2, 0)").is_err());
+}
+
+#[test]
+fn test_rest_parameter_collects_extra_arguments() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval(
+            "
+            func sum(start: i32 = 0, nums: ...i32) -> i32 {
+                let total = start;
+                while nums != Vec::new() {
+                    total += pop(nums);
+                }
+                total
+            }
+            ",
+        )
+        .unwrap();
+
+    assert_eq!(interpreter.eval("sum()"), Ok(Value::Integer(0)));
+    assert_eq!(interpreter.eval("sum(10)"), Ok(Value::Integer(10)));
+    assert_eq!(interpreter.eval("sum(10, 1, 2, 3)"), Ok(Value::Integer(16)));
+    assert_eq!(
+        interpreter.eval("func rest(items: ...dyn) { items } rest(1, \"a\")"),
+        Ok(Value::Vector(vec![
+            Value::Integer(1),
+            Value::String("a".to_string())
+        ]))
+    );
+    assert!(interpreter
+        .eval("func bad(nums: ...i32, last: i32) { last }")
+        .is_err());
+}
+
+#[test]
+fn test_enum_variants_match() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval(
+            r#"
+            enum Color { Red, Green, Blue }
+            func describe(color: Color) {
+                match color {
+                    Color::Red => "warm",
+                    Color::Blue => "cool",
+                    _ => "neutral",
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+    assert_eq!(
+        interpreter.eval("(describe(Color::Red), describe(Color::Green))"),
+        Ok(Value::Tuple(vec![
+            Value::String("warm".to_string()),
+            Value::String("neutral".to_string())
+        ]))
+    );
+    assert_eq!(
+        interpreter.eval("Color::Blue == Color::Blue"),
+        Ok(Value::Boolean(true))
+    );
+    assert_eq!(
+        interpreter.eval("let c = Color::Red; c = Color::Blue; to_string(c)"),
+        Ok(Value::String("Color::Blue".to_string()))
+    );
+    assert!(interpreter.eval("let d = Color::Red; d = 1;").is_err());
+    assert_eq!(
+        interpreter.eval("Color::Purple"),
+        Err("Enum Color has no variant Purple".to_string())
+    );
+}
+
+#[test]
+fn test_enum_payloads_bind_in_match() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval(
+            "
+            enum Shape { Empty, Square(i32) }
+            func area(shape: Shape) {
+                match shape {
+                    Shape::Square(side) => side * side,
+                    Shape::Empty => 0,
+                }
+            }
+            ",
+        )
+        .unwrap();
+
+    assert_eq!(
+        interpreter.eval("area(Shape::Square(4)) + area(Shape::Empty)"),
+        Ok(Value::Integer(16))
+    );
+    assert!(interpreter.eval("Shape::Square(\"big\")").is_err());
+    assert!(interpreter.eval("Shape::Square").is_err());
+    assert!(interpreter.eval("Shape::Empty(1)").is_err());
+}
+
+#[test]
+fn test_match_on_unknown_enum_or_variant_is_an_error() {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("enum Color { Red, Green }").unwrap();
+    assert_eq!(
+        interpreter.eval("match Color::Red { Color::Blue => 1, _ => 0 }"),
+        Err("Enum Color has no variant Blue".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("func f(c: Color) { match c { Colour::Red => 1, _ => 0 } }"),
+        Err("Undefined enum: Colour".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("enum Color { Cyan }"),
+        Err("Enum Color is already defined".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("enum Size { S } enum Size { M, L }"),
+        Err("Enum Size is already defined".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("match Color::Green { Color::Green => 1, _ => 0 }"),
+        Ok(Value::Integer(1))
+    );
+}
+
+#[test]
+fn test_type_alias_in_annotations() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.eval(
+        r#"
+        type Scores = HashMap<string, i32>;
+        func best(scores: Scores) -> i32 { get(scores, "ada") }
+        let scores: Scores = HashMap::new();
+        insert(scores, "ada", 9);
+        best(scores)
+        "#,
+    );
+    assert_eq!(result, Ok(Value::Integer(9)));
+    assert_eq!(
+        interpreter.eval("type Id = u8; let id: Id = 300;"),
+        Err("Integer 300 out of range for id of type u8".to_string())
+    );
+}
+
+#[test]
+fn test_string_interpolation() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.eval(
+        r#"
+        let name = "Ada";
+        let count = 3;
+        "Hello ${name}, you have ${count * 2 + 1} items in ${(1, "box")}, not \${count}"
+        "#,
+    );
+    assert_eq!(
+        result,
+        Ok(Value::String(
+            "Hello Ada, you have 7 items in (1, \"box\"), not ${count}".to_string()
+        ))
+    );
+    assert!(interpreter.eval(r#""${}""#).is_err());
+    assert!(interpreter.eval(r#""${1 2}""#).is_err());
+}
+
+#[test]
+fn test_try_recovers_from_runtime_errors() {
+    let mut interpreter = Interpreter::new();
+    let code = r#"
+        func divide(a: i32, b: i32) -> i32 { a / b }
+        let message = "";
+        let fallback = try { divide(1, 0) } else (err) { message = err; -1 };
+        let fine = try { divide(6, 3) } else (err) { -1 };
+        (fallback, fine, message)
+    "#;
+    assert_eq!(
+        interpreter.eval(code),
+        Ok(Value::Tuple(vec![
+            Value::Integer(-1),
+            Value::Integer(2),
+            Value::String("Division by zero".to_string()),
+        ]))
+    );
+
+    // The handler's error name doesn't leak out, and the script keeps running
+    assert!(interpreter.eval("err").is_err());
+    assert_eq!(interpreter.eval("divide(9, 3)"), Ok(Value::Integer(3)));
+
+    // An error in the handler itself propagates
+    assert_eq!(
+        interpreter.eval("try { 1 / 0 } else (err) { 2 / 0 }"),
+        Err("Division by zero".to_string())
+    );
+}
+
+#[test]
+fn test_try_builtins_return_results() {
+    let mut interpreter = Interpreter::new();
+    let code = r#"
+        let config = try_read_file("/no/such/config.txt");
+        let port = try_get(new_hashmap(), "port");
+        (is_ok(config), unwrap_or(config, "defaults"), unwrap_or(port, "8080"))
+    "#;
+    assert_eq!(
+        interpreter.eval(code),
+        Ok(Value::Tuple(vec![
+            Value::Boolean(false),
+            Value::String("defaults".to_string()),
+            Value::String("8080".to_string()),
+        ]))
+    );
+    assert_eq!(
+        interpreter.eval(r#"to_string(try_parse_int("ff", 16))"#),
+        Ok(Value::String("Ok(255)".to_string()))
+    );
+    assert_eq!(
+        interpreter.eval(r#"unwrap(try_to_int("x"))"#),
+        Err("Failed to parse string as integer: \"x\"".to_string())
+    );
+}
+
+#[test]
+fn test_bitwise_not() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.eval("~0"), Ok(Value::Integer(-1)));
+    assert_eq!(interpreter.eval("let n = 5; ~n"), Ok(Value::Integer(-6)));
+    assert_eq!(interpreter.eval("~~n + -~1"), Ok(Value::Integer(7)));
+    assert!(interpreter.eval("~true").is_err());
+
+    // `~` in a type still marks unique ownership
+    assert_eq!(
+        interpreter.eval("let data: ~i32 = ~0; let other = data; other"),
+        Ok(Value::Integer(-1))
+    );
+    assert!(interpreter.eval("data").is_err());
+}
+
+// A writer the test keeps a handle to after giving it to the interpreter
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_output_goes_to_injected_writer() {
+    let buffer = Buffer::default();
+    let mut interpreter = Interpreter::with_writer(buffer.clone());
+    let code = r#"
+        func greet(name: string) { println("Hello, ${name}!"); }
+        greet("Ada");
+        print(1, 2);
+        print("\n");
+        println(to_string((3, "x")));
+    "#;
+    assert_eq!(interpreter.eval(code), Ok(Value::Unit));
+    assert_eq!(
+        String::from_utf8(buffer.0.borrow().clone()).unwrap(),
+        "Hello, Ada!\n12\n(3, \"x\")\n"
+    );
+}
+
+#[test]
+fn test_step_budget_stops_runaway_scripts() {
+    for bytecode in [false, true] {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_bytecode(bytecode);
+        interpreter.set_max_steps(Some(1000));
+        assert_eq!(
+            interpreter.eval("while true { }"),
+            Err("Execution budget exceeded".to_string())
+        );
+    }
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_max_steps(Some(1000));
+    assert_eq!(
+        interpreter.eval("func spin(n: i32) -> i32 { if n > 0 { spin(n - 1) + spin(n - 1) } else { 0 } } spin(10)"),
+        Err("Execution budget exceeded".to_string())
+    );
+
+    // A fresh budget lets bounded work finish
+    interpreter.set_max_steps(Some(1000));
+    assert_eq!(
+        interpreter.eval("let i = 0; while i < 100 { i += 1 } i"),
+        Ok(Value::Integer(100))
+    );
+}
+
+#[test]
+fn test_sandbox_refuses_filesystem_builtins() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_sandbox(true);
+    for call in [
+        r#"read_file("Cargo.toml")"#,
+        r#"try_read_file("Cargo.toml")"#,
+        r#"write_file("sandbox.txt", "data")"#,
+        r#"remove_file("Cargo.toml")"#,
+        r#"list_dir(".")"#,
+        r#"file_exists("Cargo.toml")"#,
+    ] {
+        let name = &call[..call.find('(').unwrap()];
+        assert_eq!(
+            interpreter.eval(call),
+            Err(format!("Operation not permitted in sandbox mode: {}", name))
+        );
+    }
+
+    // Everything else still works
+    assert_eq!(
+        interpreter.eval(r#"to_int(trim(" 7 ")) * max(2, 3)"#),
+        Ok(Value::Integer(21))
+    );
+
+    interpreter.set_sandbox(false);
+    assert_eq!(
+        interpreter.eval(r#"file_exists("Cargo.toml")"#),
+        Ok(Value::Boolean(true))
+    );
+}
+
+#[test]
+fn test_trace_and_call_hooks_observe_execution() {
+    let nodes = Rc::new(RefCell::new(Vec::new()));
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    interpreter.set_constant_folding(false);
+    let recorded = nodes.clone();
+    interpreter.set_trace(move |node| {
+        // The variant name, without its fields
+        let debug = format!("{:?}", node);
+        let kind = debug.split(['(', ' ']).next().unwrap_or_default();
+        recorded.borrow_mut().push(kind.to_string());
+    });
+    let recorded = calls.clone();
+    interpreter.set_call_hook(move |event| {
+        recorded.borrow_mut().push(match event {
+            CallEvent::Enter { name, args } => format!("enter {} {:?}", name, args),
+            CallEvent::Exit { name, result } => format!("exit {} {:?}", name, result),
+        });
+    });
+
+    let code = "func double(n: i32) -> i32 { n * 2 } let x = double(1 + 2);";
+    assert_eq!(interpreter.eval(code), Ok(Value::Integer(6)));
+    assert_eq!(
+        *nodes.borrow(),
+        vec![
+            "FunctionDecl",
+            "VariableDecl",
+            "FunctionCall",
+            "BinaryOp",
+            "Integer",
+            "Integer",
+            "Block",
+            "BinaryOp",
+            "Identifier",
+            "Integer",
+        ]
+    );
+    assert_eq!(
+        *calls.borrow(),
+        vec!["enter double [Integer(3)]", "exit double Ok(Integer(6))"]
+    );
+}
+
+#[test]
+fn test_breakpoint_pauses_with_locals() {
+    let pauses = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    let recorded = pauses.clone();
+    interpreter.set_breakpoints([3], move |line, locals| {
+        let locals: Vec<String> = locals
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        recorded.borrow_mut().push((line, locals));
+    });
+
+    let code = "func area(w: i32, h: i32) -> i32 {
+    let result = w * h;
+    result
+}
+let total = area(3, 4);
+area(5, 6)";
+    assert_eq!(interpreter.eval(code), Ok(Value::Integer(30)));
+    assert_eq!(
+        *pauses.borrow(),
+        vec![
+            (3, vec!["h=4".to_string(), "result=12".into(), "w=3".into()]),
+            (3, vec!["h=6".to_string(), "result=30".into(), "w=5".into()]),
+        ]
+    );
+}
+
+#[test]
+fn test_breakpoint_in_one_line_body_and_continued_statement() {
+    let pauses = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    let recorded = pauses.clone();
+    interpreter.set_breakpoints([1, 4], move |line, locals| {
+        let locals: Vec<String> = locals.iter().map(|(name, _)| name.to_string()).collect();
+        recorded.borrow_mut().push((line, locals));
+    });
+
+    let code = "func twice(n: i32) -> i32 { n * 2 }
+let a = twice(1);
+let b = twice(
+    a);
+b";
+    assert_eq!(interpreter.eval(code), Ok(Value::Integer(4)));
+    let lines: Vec<usize> = pauses.borrow().iter().map(|(line, _)| *line).collect();
+    // Declaring `twice`, then once inside each call, then the second line
+    // of the last `let`
+    assert_eq!(lines, vec![1, 1, 4, 1]);
+    assert_eq!(pauses.borrow()[1].1, vec!["n".to_string()]);
+}
+
+#[test]
+fn test_profiler_counts_calls() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_profiling(true);
+    let code = "func helper(n: i32) -> i32 { n + 1 }
+func run() -> i32 { helper(1) + helper(2) + helper(3) }
+run()";
+    assert_eq!(interpreter.eval(code), Ok(Value::Integer(9)));
+    let counts: Vec<(String, u64)> = interpreter
+        .profile_report()
+        .into_iter()
+        .map(|(name, calls, _)| (name, calls))
+        .collect();
+    assert_eq!(
+        counts,
+        vec![("helper".to_string(), 3), ("run".to_string(), 1)]
+    );
+
+    // A recursive function's time is that of its outermost call, so it
+    // can't exceed the time of the function calling it
+    interpreter.set_profiling(true);
+    let code = "func fact(n: i32) -> i32 { if n < 2 { 1 } else { n * fact(n - 1) } }
+func outer() -> i32 { fact(8) }
+outer()";
+    assert_eq!(interpreter.eval(code), Ok(Value::Integer(40320)));
+    let report = interpreter.profile_report();
+    let time = |name: &str| report.iter().find(|(n, _, _)| n == name).unwrap().2;
+    assert_eq!(report[0].0, "fact");
+    assert_eq!(report[0].1, 8);
+    assert!(time("fact") <= time("outer"));
+
+    // Off by default
+    assert!(Interpreter::new().profile_report().is_empty());
+}
+
+#[test]
+fn test_do_while_runs_body_first() {
+    let mut interpreter = Interpreter::new();
+    let code = "let runs = 0;
+do {
+    runs += 1;
+} while false;
+runs";
+    assert_eq!(interpreter.eval(code), Ok(Value::Integer(1)));
+
+    let code = "let n = 0; do { n += 3 } while n < 10; n";
+    assert_eq!(interpreter.eval(code), Ok(Value::Integer(12)));
+
+    assert_eq!(
+        interpreter.eval("do { n += 1 } while 1"),
+        Err("Condition must be a boolean, found integer 1".to_string())
+    );
+    assert_eq!(interpreter.eval("n"), Ok(Value::Integer(13)));
+}
+
+#[test]
+fn test_nested_tuple_access() {
+    let mut interpreter = Interpreter::new();
+    let code = "let t = ((1, 2), 3); t.0.1 * 10 + t.1";
+    assert_eq!(interpreter.eval(code), Ok(Value::Integer(23)));
+}
+
+#[test]
+fn test_destructuring_checks_tuple_annotation() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        interpreter.eval("let (a, b): (i32, string) = (1, 2);"),
+        Err("Type mismatch: b has type string, found integer".to_string())
+    );
+    assert!(interpreter.eval("a").is_err());
+    assert_eq!(
+        interpreter.eval("let (a, b): (i32, string) = (1, \"x\"); b"),
+        Ok(Value::String("x".to_string()))
+    );
+    assert!(interpreter.eval("b = 2;").is_err());
+    assert_eq!(
+        interpreter.eval("let (c, d): i32 = (1, 2);"),
+        Err("Cannot destructure into 2 names with type i32".to_string())
+    );
+}
+
+#[test]
+fn test_integer_overflow_is_an_error() {
+    let mut interpreter = Interpreter::new();
+    let code = "let i = 2147483645; while i > 0 { i += 1 }";
+    assert_eq!(interpreter.eval(code), Err("Integer overflow".to_string()));
+    assert_eq!(interpreter.eval("i"), Ok(Value::Integer(i32::MAX)));
+
+    let mut compiled = Interpreter::new();
+    compiled.set_bytecode(true);
+    assert_eq!(compiled.eval(code), Err("Integer overflow".to_string()));
+
+    // Overflow in constant expressions is reported when they run, not when
+    // they're folded
+    assert_eq!(
+        interpreter.eval("func never() -> i32 { 2147483647 + 1 } 7"),
+        Ok(Value::Integer(7))
+    );
+    assert_eq!(
+        interpreter.eval("never()"),
+        Err("Integer overflow".to_string())
+    );
+    assert_eq!(
+        interpreter.eval("let m = 0 - 2147483647 - 1; -m"),
+        Err("Integer overflow".to_string())
+    );
+}
+
+#[test]
+fn test_mutating_builtins_check_element_types() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval(
+            "let v: Vec<i32> = Vec::new(); push(v, 1);
+             let w: @Vec<u8> = Vec::new(); push(w, 2);
+             let m: HashMap<string, bool> = HashMap::new(); insert(m, \"k\", true);",
+        )
+        .unwrap();
+    for (code, message) in [
+        (
+            "push(v, \"x\")",
+            "Type mismatch: element of v has type i32, found string",
+        ),
+        (
+            "set(v, 0, 1.5)",
+            "Type mismatch: element of v has type i32, found float",
+        ),
+        (
+            "push(w, 300)",
+            "Integer 300 out of range for element of w of type u8",
+        ),
+        (
+            "insert(m, \"k\", 1)",
+            "Type mismatch: element of m has type bool, found integer",
+        ),
+    ] {
+        assert_eq!(interpreter.eval(code), Err(message.to_string()), "{}", code);
+    }
+    assert_eq!(
+        interpreter.eval("to_string(v)"),
+        Ok(Value::String("[1]".into()))
+    );
+    assert_eq!(
+        interpreter.eval("to_string(w)"),
+        Ok(Value::String("[2]".into()))
+    );
+}
+
+#[test]
+fn test_type_aliases_persist_between_evals() {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("type Id = u8;").unwrap();
+    assert_eq!(interpreter.eval("let a: Id = 5; a"), Ok(Value::Integer(5)));
+    assert_eq!(
+        interpreter.eval("let b: Id = 300;"),
+        Err("Integer 300 out of range for b of type u8".to_string())
+    );
+
+    interpreter.reset();
+    assert!(interpreter.eval("let c: Id = 5;").is_err());
+}
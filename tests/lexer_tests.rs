@@ -1 +1,29 @@
+use animikiikode::lexer::Token;
+use animikiikode::tokenize;
 
+#[test]
+fn test_tokenize_program() {
+    assert_eq!(
+        tokenize("let x = add(1, 2.5);"),
+        Ok(vec![
+            Token::Let,
+            Token::Identifier("x".to_string()),
+            Token::Assign,
+            Token::Identifier("add".to_string()),
+            Token::LParen,
+            Token::Integer(1),
+            Token::Comma,
+            Token::Float(2.5),
+            Token::RParen,
+            Token::Semicolon,
+        ])
+    );
+}
+
+#[test]
+fn test_tokenize_reports_invalid_character_position() {
+    assert_eq!(
+        tokenize("let a = 1;\nlet b = a & 2;"),
+        Err("Invalid character: & at line 2, column 11".to_string())
+    );
+}
@@ -1 +1,51 @@
+use animikiikode::parse;
+use animikiikode::parser::{AstNode, Operator};
 
+#[test]
+fn test_parse_program() {
+    assert_eq!(
+        parse("let x = 1 + 2; x"),
+        Ok(vec![
+            AstNode::VariableDecl {
+                name: "x".into(),
+                type_annotation: None,
+                initializer: Some(Box::new(AstNode::BinaryOp {
+                    left: Box::new(AstNode::Integer(1)),
+                    operator: Operator::Add,
+                    right: Box::new(AstNode::Integer(2)),
+                })),
+                ownership: None,
+            },
+            AstNode::Identifier("x".into()),
+        ])
+    );
+}
+
+#[test]
+fn test_parse_reports_syntax_errors() {
+    assert!(parse("let = 5;").is_err());
+}
+
+#[test]
+fn test_unimplemented_keywords_are_reported() {
+    let cases = [
+        ("for x in v { x }", "for"),
+        ("return 1;", "return"),
+        ("mod util;", "mod"),
+        ("pub func f() { }", "pub"),
+        ("use util::helper;", "use"),
+        ("struct Point { x: i32 }", "struct"),
+        ("impl Point { }", "impl"),
+    ];
+    for (source, keyword) in cases {
+        assert_eq!(
+            parse(source),
+            Err(format!(
+                "Unexpected keyword '{}': reserved but not supported yet",
+                keyword
+            )),
+            "{}",
+            source
+        );
+    }
+}